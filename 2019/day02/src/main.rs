@@ -1,71 +1,28 @@
-fn main() {
-    let input_mem = include_str!("input.txt");
-    let memory = input_mem.split(',')
-        .map(|x| x.parse::<i32>().unwrap())
-        .collect::<Vec<_>>();
+extern crate int_code;
+use int_code::*;
 
-    {
-        let mut memory_1202 = memory.to_vec();
-        memory_1202[1] = 12;
-        memory_1202[2] = 2;
-        execute(&mut memory_1202);
-        println!("Repaired value: {}", memory_1202[0]);
-    }
-    for noun in 0..=99 {
-        for verb in 0..=99 {
-            let mut memory_nv = memory.to_vec();
-            memory_nv[1] = noun;
-            memory_nv[2] = verb;
-            execute(&mut memory_nv);
-            if memory_nv[0] == 19690720 {
-                println!("100 * {} + {} = {}", noun, verb, 100 * noun + verb);
-            }
-        }
-    }
-}
+const NOUN: Word = 1;
+const VERB: Word = 2;
 
-fn execute(memory:&mut [i32]) {
-    for pc in (0..memory.len()).step_by(4) {
-        let opcode = memory[pc];
-        if opcode == 99 {
-            break;
-        }
-        let val1_address = memory[pc + 1] as usize;
-        let val2_address = memory[pc + 2] as usize;
-        let storage_address = memory[pc + 3] as usize;
+fn main() {
+    let memory = parse_csv(include_str!("input.txt")).unwrap();
+
+    let repaired = run_patched(&memory, &[(NOUN, 12), (VERB, 2)], 1000).unwrap_or_else(|e| panic!("{}", e));
+    println!("Repaired value: {}", repaired);
 
-        let val1 = memory[val1_address];
-        let val2 = memory[val2_address];
-        if opcode == 1 {
-            memory[storage_address] = val1 + val2;
-        }
-        else if opcode == 2 {
-            memory[storage_address] = val1 * val2;
-        }
-        else {
-            panic!("unrecognised opcode {} at address {}", opcode, pc);
-        }
+    match search_patches(&memory, &[NOUN, VERB], 0..=99, 19690720, 1000).as_deref() {
+        Some(&[noun, verb]) => println!("100 * {} + {} = {}", noun, verb, 100 * noun + verb),
+        _ => println!("No noun and verb give 19690720")
     }
 }
 
-#[allow(dead_code)]
-fn dump(mem:&[i32]) {
-    let mut i = 0;
-    for memory_value in mem {
-        if i == 0 {
-            print!("{}",memory_value);
-            i += 1;
-        }
-        else {
-            print!("\t{}", memory_value);
-            i += 1;
-            if i == 4 {
-                println!();
-                i = 0;
-            }
-        }
-    }
-    if i == 1 {
-        println!();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(Ok(3500), run_patched(&parse_csv("1,9,10,3,2,3,11,0,99,30,40,50").unwrap(), &[], 10));
+        assert_eq!(Ok(30), run_patched(&parse_csv("1,1,1,4,99,5,6,0,99").unwrap(), &[], 10));
     }
 }
@@ -6,14 +6,15 @@ fn main() {
     {
         let (mut machine, input, output) = Machine::new(&mem);
         input.send(1).unwrap();
-        machine.execute(1000).unwrap();
+        machine.execute(1000).unwrap_or_else(|e| panic!("{}", e));
         let result = output.try_iter().collect::<Vec<Word>>();
         println!("Part 1: {:?}", result);
     }
     {
         let (mut machine, input, output) = Machine::new(&mem);
+        machine.set_engine(Engine::Cached);
         input.send(2).unwrap();
-        machine.execute(1000000).unwrap();
+        machine.execute(1000000).unwrap_or_else(|e| panic!("{}", e));
         let result = output.try_iter().collect::<Vec<Word>>();
         println!("Part 2: {:?}", result);
     }
@@ -35,25 +35,23 @@ fn main() {
     println!();
 }
 
-fn paint(init_mem: &Vec<Word>, init_colour: Colour) -> HashMap<Point, Colour> {
-    let (mut machine, input, output) = Machine::new(init_mem);
+fn paint(init_mem: &[Word], init_colour: Colour) -> HashMap<Point, Colour> {
+    let (machine, input, output) = Machine::new(init_mem);
+    let robot = machine.spawn();
     let mut direction = Direction::Up;
     let mut paints = HashMap::<Point, Colour>::new();
     let mut position = Point{x: 0, y: 0};
     paints.insert(position, init_colour);
     loop {
-        let mut halt = false;
-
         let cur_colour = paints.get(&position).unwrap_or(&Colour::Black);
-
-        input.send(cur_colour.to_word()).unwrap();
-        match machine.execute(10000) {
-            Ok(_) => halt = true,
-            Err(ExecuteError::InputRequired) => {},
-            Err(e) => panic!("{}", e),
+        // Once the robot halts its input is gone, and so is its output
+        if input.send(cur_colour.to_word()).is_err() {
+            break;
         }
-
-        let new_colour = Colour::from_word(output.recv().unwrap());
+        let new_colour = match output.recv() {
+            Ok(w) => Colour::from_word(w),
+            Err(_) => break
+        };
         paints.insert(position, new_colour);
 
         match output.recv().unwrap() {
@@ -63,10 +61,9 @@ fn paint(init_mem: &Vec<Word>, init_colour: Colour) -> HashMap<Point, Colour> {
         }
 
         position.move_dir(direction);
-
-        if halt {
-            break;
-        }
+    }
+    if let Err(e) = robot.join().unwrap() {
+        panic!("{}", e);
     }
     paints
 }
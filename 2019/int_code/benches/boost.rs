@@ -0,0 +1,34 @@
+extern crate int_code;
+use int_code::*;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Day 9 part 2 runs the BOOST program in sensor boost mode, which takes a few hundred thousand steps
+const RUNS: u32 = 10;
+
+fn main() {
+    let program = parse_csv(include_str!("../../day09/src/input.txt").trim()).unwrap();
+
+    let (expected, baseline) = time(&program, Engine::Interpreter, Backend::Sparse);
+    println!("BOOST sensor mode, {} runs, output {:?}", RUNS, expected);
+    for &engine in [Engine::Interpreter, Engine::Cached].iter() {
+        for &backend in Backend::ALL.iter() {
+            let (output, elapsed) = time(&program, engine, backend);
+            assert_eq!(expected, output);
+            let name = format!("{:?}/{:?}:", engine, backend).to_lowercase();
+            println!("{:<20}{:>10.3?} per run ({:.2}x)", name, elapsed / RUNS, baseline.as_secs_f64() / elapsed.as_secs_f64());
+        }
+    }
+}
+
+fn time(program: &[Word], engine: Engine, backend: Backend) -> (Vec<Word>, Duration) {
+    let mut output = Vec::new();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        let mut machine = Machine::with_backend(program, backend, VecDeque::from(vec![2]), Vec::new());
+        machine.set_engine(engine);
+        machine.execute(1000000).unwrap_or_else(|e| panic!("{}", e));
+        output = machine.into_io().1;
+    }
+    (output, start.elapsed())
+}
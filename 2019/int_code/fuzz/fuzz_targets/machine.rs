@@ -0,0 +1,11 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use int_code::FuzzCase;
+
+// Run with cargo +nightly fuzz run machine
+fuzz_target!(|data: &[u8]| {
+    let case = FuzzCase::from_bytes(data);
+    if let Err(message) = case.check() {
+        panic!("{}\n{:?}", message, case);
+    }
+});
@@ -0,0 +1,320 @@
+use crate::{Machine, Word};
+#[cfg(feature = "bigint")]
+use crate::{Memory, Operation, Operation::*, Parameter, StepError, StepResult, WordSource, WordSink, advance};
+#[cfg(feature = "bigint")]
+use num_bigint::{BigInt, Sign};
+#[cfg(feature = "bigint")]
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Arithmetic {
+    // ADD and MUL fail with ArithmeticOverflow when the result doesn't fit in a Word
+    Checked,
+    // Results wrap around in two's complement
+    Wrapping,
+    // Results are clamped to the range of a Word
+    Saturating,
+    // Results are kept exactly. Memory holds the saturated value of anything too big for a Word,
+    // and using one as an address, jump target, relative base offset or output is an
+    // ArithmeticOverflow. Only available with the bigint feature.
+    Unbounded
+}
+
+// An arithmetic mode this build of the crate can't run
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct UnsupportedArithmetic(pub Arithmetic);
+
+impl Arithmetic {
+    pub const ALL: [Arithmetic; 4] = [Arithmetic::Checked, Arithmetic::Wrapping, Arithmetic::Saturating, Arithmetic::Unbounded];
+
+    pub fn supported(self) -> bool {
+        cfg!(feature = "bigint") || self != Arithmetic::Unbounded
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Arithmetic::Checked => "checked",
+            Arithmetic::Wrapping => "wrapping",
+            Arithmetic::Saturating => "saturating",
+            Arithmetic::Unbounded => "unbounded",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Arithmetic> {
+        Arithmetic::ALL.iter().cloned().find(|a| a.name() == name)
+    }
+
+    pub(crate) fn add(self, a: Word, b: Word) -> Option<Word> {
+        match self {
+            Arithmetic::Wrapping => Some(a.wrapping_add(b)),
+            Arithmetic::Saturating => Some(a.saturating_add(b)),
+            _ => a.checked_add(b)
+        }
+    }
+
+    pub(crate) fn mul(self, a: Word, b: Word) -> Option<Word> {
+        match self {
+            Arithmetic::Wrapping => Some(a.wrapping_mul(b)),
+            Arithmetic::Saturating => Some(a.saturating_mul(b)),
+            _ => a.checked_mul(b)
+        }
+    }
+}
+
+// Exact values of the memory cells holding results too big for a Word. Only ever non-empty in
+// Unbounded mode.
+#[derive(Clone, Default)]
+pub(crate) struct BigCells {
+    #[cfg(feature = "bigint")]
+    cells: HashMap<Word, BigInt>
+}
+
+impl BigCells {
+    #[cfg(feature = "bigint")]
+    pub(crate) fn forget(&mut self, address: Word) {
+        self.cells.remove(&address);
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    pub(crate) fn forget(&mut self, _address: Word) {
+    }
+
+    fn clear(&mut self) {
+        #[cfg(feature = "bigint")]
+        self.cells.clear();
+    }
+
+    // The exact values in decimal, in address order, for saving
+    #[cfg(feature = "bigint")]
+    pub(crate) fn to_decimal(&self) -> Vec<(Word, String)> {
+        let mut cells = self.cells.iter().map(|(&a, v)| (a, v.to_string())).collect::<Vec<_>>();
+        cells.sort();
+        cells
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    pub(crate) fn to_decimal(&self) -> Vec<(Word, String)> {
+        Vec::new()
+    }
+
+    // Reads back saved exact values, each of which has to be too big for a Word
+    #[cfg(feature = "bigint")]
+    pub(crate) fn from_decimal(cells: &[(Word, String)]) -> Result<BigCells, String> {
+        let mut big = BigCells::default();
+        for (address, text) in cells {
+            let value = text.parse::<BigInt>().map_err(|_| format!("bad exact value at {}", address))?;
+            if word(&value).is_some() {
+                return Err(format!("exact value at {} fits in a word", address));
+            }
+            big.cells.insert(*address, value);
+        }
+        Ok(big)
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    pub(crate) fn from_decimal(cells: &[(Word, String)]) -> Result<BigCells, String> {
+        match cells.first() {
+            None => Ok(BigCells::default()),
+            Some(_) => Err("exact values need the bigint feature".to_string())
+        }
+    }
+}
+
+impl fmt::Display for UnsupportedArithmetic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} arithmetic needs the bigint feature", self.0.name())
+    }
+}
+impl StdError for UnsupportedArithmetic {}
+
+impl<I, O> Machine<I, O> {
+    // Leaving Unbounded mode keeps the saturated values in memory and drops the exact ones
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) -> Result<(), UnsupportedArithmetic> {
+        if !arithmetic.supported() {
+            return Err(UnsupportedArithmetic(arithmetic));
+        }
+        if arithmetic != self.arithmetic {
+            self.big.clear();
+        }
+        self.arithmetic = arithmetic;
+        Ok(())
+    }
+
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    // The exact value at an address, which may be too big for a Word in Unbounded mode
+    #[cfg(feature = "bigint")]
+    pub fn read_exact(&self, address: Word) -> Option<BigInt> {
+        match self.big.cells.get(&address) {
+            Some(value) => Some(value.clone()),
+            None => self.memory.read_position(address).ok().map(BigInt::from)
+        }
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl<I: WordSource, O: WordSink> Machine<I, O> {
+    // Performs the operations that can take or produce values too big for a Word. Returns None for
+    // anything else, which is performed as normal.
+    pub(crate) fn perform_unbounded(&mut self, op: Operation) -> Result<Option<StepResult>, StepError> {
+        match op {
+            Add(a, b, out) | Multiply(a, b, out) | LessThan(a, b, out) | Equals(a, b, out) => {
+                let a_val = self.read_big(a)?;
+                let b_val = self.read_big(b)?;
+                let result = match op {
+                    Add(..) => a_val + b_val,
+                    Multiply(..) => a_val * b_val,
+                    LessThan(..) => BigInt::from((a_val < b_val) as Word),
+                    _ => BigInt::from((a_val == b_val) as Word),
+                };
+                let address = Memory::address(out.0, out.1)? as Word;
+                let small = word(&result);
+                let saturated = small.unwrap_or(if result.sign() == Sign::Minus { Word::MIN } else { Word::MAX });
+                self.memory.write(out, saturated)?;
+                if small.is_some() {
+                    self.big.forget(address);
+                }
+                else {
+                    self.big.cells.insert(address, result);
+                }
+                self.pc = advance(self.pc, 4)?;
+            },
+            // A value too big for a Word is never zero
+            JumpIfTrue(a, new_pc) | JumpIfFalse(a, new_pc) if self.is_big(a) => {
+                if let JumpIfTrue(..) = op {
+                    if self.is_big(new_pc) {
+                        return Err(StepError::ArithmeticOverflow);
+                    }
+                    self.pc = self.memory.read(new_pc)?;
+                }
+                else {
+                    self.pc = advance(self.pc, 3)?;
+                }
+            },
+            _ => {
+                if op.reads(&self.memory).iter().flatten().any(|&p| self.is_big(p)) {
+                    return Err(StepError::ArithmeticOverflow);
+                }
+                return Ok(None);
+            }
+        }
+        self.waiting_input = false;
+        Ok(Some(StepResult::Executed))
+    }
+
+    fn is_big(&self, parameter: Parameter) -> bool {
+        parameter.address().is_some_and(|a| self.big.cells.contains_key(&a))
+    }
+
+    fn read_big(&self, parameter: Parameter) -> Result<BigInt, StepError> {
+        match parameter.address().and_then(|a| self.big.cells.get(&a)) {
+            Some(value) => Ok(value.clone()),
+            None => Ok(BigInt::from(self.memory.read(parameter)?))
+        }
+    }
+}
+
+#[cfg(feature = "bigint")]
+fn word(value: &BigInt) -> Option<Word> {
+    use std::convert::TryFrom;
+    Word::try_from(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_csv, ExecuteError};
+    #[cfg(feature = "bigint")]
+    use crate::{Call, SaveFile, Slot};
+    use std::collections::VecDeque;
+
+    // Squares 2^32 and outputs the result less one
+    const SQUARE: &str = "2,13,13,14,1001,14,-1,15,4,15,99,0,0,4294967296,0,0";
+
+    fn run(arithmetic: Arithmetic, csv: &str) -> (Result<(), ExecuteError>, Vec<Word>) {
+        let mut machine = Machine::with_io(&parse_csv(csv).unwrap(), VecDeque::new(), Vec::new());
+        machine.set_arithmetic(arithmetic).unwrap();
+        let result = machine.execute(100).map_err(|f| f.error);
+        (result, machine.into_io().1)
+    }
+
+    #[test]
+    fn test_modes() {
+        assert_eq!((Err(ExecuteError::ArithmeticOverflow), vec![]), run(Arithmetic::Checked, SQUARE));
+        assert_eq!((Ok(()), vec![-1]), run(Arithmetic::Wrapping, SQUARE));
+        assert_eq!((Ok(()), vec![Word::MAX - 1]), run(Arithmetic::Saturating, SQUARE));
+        assert_eq!((Ok(()), vec![Word::MIN]), run(Arithmetic::Wrapping, "1101,9223372036854775807,1,7,4,7,99,0"));
+        assert_eq!((Ok(()), vec![Word::MIN]), run(Arithmetic::Saturating, "1102,-9223372036854775807,2,7,4,7,99,0"));
+
+        // The relative base is an address, so it is checked whatever the mode
+        assert_eq!(Err(ExecuteError::ArithmeticOverflow), run(Arithmetic::Wrapping, "109,9223372036854775807,109,1,99").0);
+
+        let (mut machine, _, _) = Machine::new(&[99]);
+        let unbounded = machine.set_arithmetic(Arithmetic::Unbounded);
+        assert_eq!(cfg!(feature = "bigint"), unbounded.is_ok());
+        assert_eq!(Some(Arithmetic::Saturating), Arithmetic::from_name("saturating"));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_unbounded() {
+        let program = crate::assemble("
+                MUL  x, x, x
+                MUL  x, x, y
+                LT   y, x, lt
+                EQ   y, y, eq
+                JT   y, #next
+                HLT
+        next:   MUL  y, #0, z
+                ADD  z, #7, z
+                OUT  z
+                HLT
+        x:      .data 4294967296
+        y:      .data 0
+        z:      .data 0
+        lt:     .data 5
+        eq:     .data 0").unwrap();
+        let mut machine = Machine::with_io(&program, VecDeque::new(), Vec::new());
+        machine.set_arithmetic(Arithmetic::Unbounded).unwrap();
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(&vec![7], machine.output());
+        let x = BigInt::from(1) << 64;
+        let y = &x * &x;
+        assert_eq!((Some(x), Some(y)), (machine.read_exact(31), machine.read_exact(32)));
+        assert_eq!(Ok(Word::MAX), machine.memory.read_position(32).map_err(|_| ()));
+        assert_eq!((Some(BigInt::from(0)), Some(BigInt::from(1))), (machine.read_exact(34), machine.read_exact(35)));
+
+        // An oversized value can't be output, but can be brought back into range
+        assert_eq!((Err(ExecuteError::ArithmeticOverflow), vec![]), run(Arithmetic::Unbounded, "2,7,7,7,4,7,99,4294967296"));
+        assert_eq!((Ok(()), vec![1]), run(Arithmetic::Unbounded, "2,11,11,11,1002,11,0,11,104,1,99,4294967296"));
+        // An untaken jump never reads its oversized target
+        assert_eq!((Ok(()), vec![]), run(Arithmetic::Unbounded, "2,9,9,9,5,10,9,99,0,4294967296,0"));
+
+        machine.set_arithmetic(Arithmetic::Checked).unwrap();
+        assert_eq!(Some(BigInt::from(Word::MAX)), machine.read_exact(32));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_saved_and_overwritten() {
+        let mut machine = Machine::with_io(&parse_csv("2,11,11,11,20,11,99,0,0,0,0,4294967296").unwrap(), VecDeque::new(), Vec::new());
+        machine.set_arithmetic(Arithmetic::Unbounded).unwrap();
+        machine.register(20, &[Slot::Write], |call: &mut Call| call.write(3)).unwrap();
+        assert_eq!(Ok(()), machine.step().map(|_| ()));
+        let exact = Some(BigInt::from(1) << 64);
+
+        let save = machine.save_state();
+        for loaded in [SaveFile::from_json(&save.to_json()), SaveFile::from_bytes(&save.to_bytes())] {
+            let resumed = loaded.unwrap().resume_buffered();
+            assert_eq!((Arithmetic::Unbounded, exact.clone()), (resumed.arithmetic(), resumed.read_exact(11)));
+        }
+
+        // A custom instruction's write replaces the exact value like any other
+        assert_eq!(Ok(()), machine.execute(10));
+        assert_eq!(Some(BigInt::from(3)), machine.read_exact(11));
+    }
+}
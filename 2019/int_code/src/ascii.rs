@@ -0,0 +1,134 @@
+use crate::{Machine, WordSource, WordSink, ReadError, WriteError, Word};
+use std::collections::VecDeque;
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Text {
+    // A line of output, without its newline
+    Line(String),
+    // A word outside the ASCII range, usually a final answer
+    Value(Word)
+}
+
+// Input made up of newline terminated lines
+#[derive(Clone, Default, Debug)]
+pub struct AsciiInput {
+    queue: VecDeque<Word>
+}
+
+// Output collected into lines, with non-ASCII words kept in order between them
+#[derive(Clone, Default, Debug)]
+pub struct AsciiOutput {
+    text: Vec<Text>,
+    partial: String
+}
+
+impl AsciiInput {
+    pub fn new() -> AsciiInput {
+        AsciiInput::default()
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        self.queue.extend(line.bytes().map(Word::from));
+        self.queue.push_back(Word::from(b'\n'));
+    }
+}
+
+impl WordSource for AsciiInput {
+    fn read(&mut self) -> Result<Word, ReadError> {
+        self.queue.read()
+    }
+
+    fn queued(&self) -> Vec<Word> {
+        self.queue.queued()
+    }
+}
+
+impl AsciiOutput {
+    pub fn new() -> AsciiOutput {
+        AsciiOutput::default()
+    }
+
+    // Completed lines and values, oldest first
+    pub fn take(&mut self) -> Vec<Text> {
+        self.text.drain(..).collect()
+    }
+
+    // Text written since the last newline, such as a prompt
+    pub fn partial(&self) -> &str {
+        &self.partial
+    }
+}
+
+impl WordSink for AsciiOutput {
+    fn write(&mut self, value: Word) -> Result<(), WriteError> {
+        match value {
+            10 => {
+                let line = std::mem::take(&mut self.partial);
+                self.text.push(Text::Line(line));
+            },
+            0..=127 => self.partial.push(value as u8 as char),
+            _ => {
+                if !self.partial.is_empty() {
+                    let line = std::mem::take(&mut self.partial);
+                    self.text.push(Text::Line(line));
+                }
+                self.text.push(Text::Value(value));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Machine<AsciiInput, AsciiOutput> {
+    pub fn ascii(memory: &[Word]) -> Machine<AsciiInput, AsciiOutput> {
+        Machine::with_io(memory, AsciiInput::new(), AsciiOutput::new())
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        self.input_mut().push_line(line);
+    }
+
+    pub fn take_text(&mut self) -> Vec<Text> {
+        self.output_mut().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, ExecuteError};
+
+    #[test]
+    fn test_prompt_and_echo() {
+        // Prints "?" then echoes a line of input, then prints 1000
+        let program = assemble("
+                OUT  #63
+                OUT  #10
+        loop:   IN   c
+                OUT  c
+                EQ   c, #10, t
+                JF   t, #loop
+                OUT  #1000
+                HLT
+        c:      .data 0
+        t:      .data 0").unwrap();
+        let mut machine = Machine::ascii(&program);
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(100).map_err(|f| f.error));
+        assert_eq!(vec![Text::Line("?".to_string())], machine.take_text());
+
+        machine.send_line("hello");
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(vec![Text::Line("hello".to_string()), Text::Value(1000)], machine.take_text());
+        assert!(machine.take_text().is_empty());
+    }
+
+    #[test]
+    fn test_partial_lines() {
+        let mut output = AsciiOutput::new();
+        for &w in [b'>' as Word, b' ' as Word, -5, b'o' as Word, b'k' as Word].iter() {
+            output.write(w).unwrap();
+        }
+        assert_eq!("ok", output.partial());
+        assert_eq!(vec![Text::Line("> ".to_string()), Text::Value(-5)], output.take());
+    }
+}
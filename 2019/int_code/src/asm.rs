@@ -0,0 +1,338 @@
+use crate::{Mnemonic, Mode, Word};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::error::Error as StdError;
+use std::fmt;
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+enum Value {
+    Number(Word),
+    Label(String, Word)
+}
+
+struct Arg {
+    mode: Mode,
+    value: Value,
+    line: usize,
+    column: usize
+}
+
+enum Statement {
+    Op(Mnemonic, Vec<Arg>),
+    Data(Vec<Arg>)
+}
+
+impl AsmError {
+    fn new<S: Into<String>>(line: usize, column: usize, message: S) -> AsmError {
+        AsmError{ line, column, message: message.into() }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+impl StdError for AsmError {}
+
+// Source is one statement per line, optionally preceded by a "label:" and followed by a "; comment".
+// Operands are written as in the disassembly listing: "21" or "label" for position mode, "#21" or
+// "#label" for immediate mode, and "@rb", "@rb+3", "@rb-1" for relative mode. Labels may carry a
+// "+n"/"-n" offset. ".data" takes a comma separated list of numbers or labels.
+pub fn assemble(source: &str) -> Result<Vec<Word>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = match text.find(';') {
+            Some(end) => &text[..end],
+            None => text
+        };
+
+        let mut pos = skip_whitespace(text, 0);
+        let (word_start, word) = next_word(text, pos);
+        if let Some(label) = word.strip_suffix(':') {
+            if !is_identifier(label) {
+                return Err(AsmError::new(line, word_start + 1, format!("invalid label '{}'", label)));
+            }
+            match labels.entry(label.to_string()) {
+                Entry::Occupied(_) => return Err(AsmError::new(line, word_start + 1, format!("duplicate label '{}'", label))),
+                Entry::Vacant(e) => { e.insert(address); }
+            }
+            pos = skip_whitespace(text, word_start + word.len());
+        }
+
+        let (word_start, word) = next_word(text, pos);
+        if word.is_empty() {
+            continue;
+        }
+        let args = parse_args(text, word_start + word.len(), line)?;
+        let statement = if word == ".data" {
+            if let Some(arg) = args.iter().find(|a| a.mode != Mode::Position) {
+                return Err(AsmError::new(line, arg.column, ".data values must be plain numbers or labels"));
+            }
+            Statement::Data(args)
+        }
+        else {
+            let mnemonic = parse_mnemonic(word)
+                .ok_or_else(|| AsmError::new(line, word_start + 1, format!("unknown mnemonic '{}'", word)))?;
+            check_args(mnemonic, &args, line, word_start + 1)?;
+            Statement::Op(mnemonic, args)
+        };
+        address += match &statement {
+            Statement::Op(_, args) => args.len() + 1,
+            Statement::Data(args) => args.len()
+        };
+        statements.push(statement);
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for statement in statements {
+        match statement {
+            Statement::Op(mnemonic, args) => {
+                let mut opcode = opcode(mnemonic);
+                let mut scale = 100;
+                for arg in args.iter() {
+                    opcode += scale * match arg.mode {
+                        Mode::Position => 0,
+                        Mode::Immediate => 1,
+                        Mode::Relative => 2,
+                    };
+                    scale *= 10;
+                }
+                program.push(opcode);
+                for arg in args.iter() {
+                    program.push(resolve(arg, &labels)?);
+                }
+            },
+            Statement::Data(args) => {
+                for arg in args.iter() {
+                    program.push(resolve(arg, &labels)?);
+                }
+            }
+        }
+    }
+    Ok(program)
+}
+
+fn opcode(mnemonic: Mnemonic) -> Word {
+    match mnemonic {
+        Mnemonic::Add => 1,
+        Mnemonic::Mul => 2,
+        Mnemonic::In => 3,
+        Mnemonic::Out => 4,
+        Mnemonic::Jt => 5,
+        Mnemonic::Jf => 6,
+        Mnemonic::Lt => 7,
+        Mnemonic::Eq => 8,
+        Mnemonic::Arb => 9,
+        Mnemonic::Hlt => 99,
+        Mnemonic::Ext(opcode) => opcode,
+    }
+}
+
+fn parse_mnemonic(word: &str) -> Option<Mnemonic> {
+    match word.to_ascii_uppercase().as_str() {
+        "ADD" => Some(Mnemonic::Add),
+        "MUL" => Some(Mnemonic::Mul),
+        "IN" => Some(Mnemonic::In),
+        "OUT" => Some(Mnemonic::Out),
+        "JT" => Some(Mnemonic::Jt),
+        "JF" => Some(Mnemonic::Jf),
+        "LT" => Some(Mnemonic::Lt),
+        "EQ" => Some(Mnemonic::Eq),
+        "ARB" => Some(Mnemonic::Arb),
+        "HLT" => Some(Mnemonic::Hlt),
+        _ => None
+    }
+}
+
+fn check_args(mnemonic: Mnemonic, args: &[Arg], line: usize, column: usize) -> Result<(), AsmError> {
+    let (count, output) = match mnemonic {
+        Mnemonic::Add | Mnemonic::Mul | Mnemonic::Lt | Mnemonic::Eq => (3, Some(2)),
+        Mnemonic::In => (1, Some(0)),
+        Mnemonic::Out | Mnemonic::Arb => (1, None),
+        Mnemonic::Jt | Mnemonic::Jf => (2, None),
+        Mnemonic::Hlt => (0, None),
+        Mnemonic::Ext(_) => return Err(AsmError::new(line, column, "custom instructions cannot be assembled".to_string())),
+    };
+    if args.len() != count {
+        return Err(AsmError::new(line, column, format!("{} takes {} operand(s), found {}", mnemonic, count, args.len())));
+    }
+    if let Some(out) = output {
+        if args[out].mode == Mode::Immediate {
+            return Err(AsmError::new(line, args[out].column, format!("{} cannot write to an immediate operand", mnemonic)));
+        }
+    }
+    Ok(())
+}
+
+fn resolve(arg: &Arg, labels: &HashMap<String, usize>) -> Result<Word, AsmError> {
+    match &arg.value {
+        Value::Number(n) => Ok(*n),
+        Value::Label(name, offset) => {
+            let address = labels.get(name)
+                .ok_or_else(|| AsmError::new(arg.line, arg.column, format!("undefined label '{}'", name)))?;
+            (*address as Word).checked_add(*offset)
+                .ok_or_else(|| AsmError::new(arg.line, arg.column, "label offset out of range"))
+        }
+    }
+}
+
+fn parse_args(text: &str, start: usize, line: usize) -> Result<Vec<Arg>, AsmError> {
+    let mut args = Vec::new();
+    if text[start..].trim().is_empty() {
+        return Ok(args);
+    }
+    let mut pos = start;
+    for piece in text[start..].split(',') {
+        let lead = piece.len() - piece.trim_start().len();
+        let column = pos + lead + 1;
+        let trimmed = piece.trim();
+        if trimmed.is_empty() {
+            return Err(AsmError::new(line, column, "expected operand"));
+        }
+        args.push(parse_arg(trimmed, line, column)?);
+        pos += piece.len() + 1;
+    }
+    Ok(args)
+}
+
+fn parse_arg(piece: &str, line: usize, column: usize) -> Result<Arg, AsmError> {
+    if let Some(rest) = piece.strip_prefix('#') {
+        let value = parse_value(rest.trim_start(), line, column + 1)?;
+        Ok(Arg{ mode: Mode::Immediate, value, line, column })
+    }
+    else if let Some(rest) = piece.strip_prefix("@rb") {
+        let rest = rest.trim_start();
+        let offset = if rest.is_empty() {
+            0
+        }
+        else if rest.starts_with('+') || rest.starts_with('-') {
+            let digits: String = rest.chars().filter(|c| !c.is_whitespace()).collect();
+            digits.trim_start_matches('+').parse::<Word>()
+                .map_err(|_| AsmError::new(line, column, format!("invalid relative offset '{}'", rest)))?
+        }
+        else {
+            return Err(AsmError::new(line, column, format!("invalid relative operand '{}'", piece)));
+        };
+        Ok(Arg{ mode: Mode::Relative, value: Value::Number(offset), line, column })
+    }
+    else {
+        let value = parse_value(piece, line, column)?;
+        Ok(Arg{ mode: Mode::Position, value, line, column })
+    }
+}
+
+fn parse_value(text: &str, line: usize, column: usize) -> Result<Value, AsmError> {
+    if let Ok(n) = text.parse::<Word>() {
+        return Ok(Value::Number(n));
+    }
+    let (name, offset) = match text.find(['+', '-']) {
+        Some(i) => {
+            let digits: String = text[i..].chars().filter(|c| !c.is_whitespace()).collect();
+            let offset = digits.trim_start_matches('+').parse::<Word>()
+                .map_err(|_| AsmError::new(line, column + i, format!("invalid label offset '{}'", &text[i..])))?;
+            (text[..i].trim_end(), offset)
+        },
+        None => (text, 0)
+    };
+    if is_identifier(name) {
+        Ok(Value::Label(name.to_string(), offset))
+    }
+    else {
+        Err(AsmError::new(line, column, format!("invalid operand '{}'", text)))
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false
+    }
+}
+
+fn skip_whitespace(text: &str, from: usize) -> usize {
+    match text[from..].find(|c: char| !c.is_whitespace()) {
+        Some(i) => from + i,
+        None => text.len()
+    }
+}
+
+fn next_word(text: &str, from: usize) -> (usize, &str) {
+    let end = match text[from..].find(char::is_whitespace) {
+        Some(i) => from + i,
+        None => text.len()
+    };
+    (from, &text[from..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{listing, Machine};
+
+    #[test]
+    fn test_compare_program() {
+        // The "compare to 8" program from the crate tests, rewritten with labels
+        let source = "
+                IN   input
+                EQ   input, #8, result
+                JT   result, #equal
+                LT   #8, input, result
+                JF   result, #less
+                ADD  #1000, #1, result
+                OUT  result
+                JT   #1, #end
+        less:   OUT  #999
+                JT   #1, #end
+        equal:  MUL  input, #125, result
+                OUT  result
+        end:    HLT
+        input:  .data 0
+        result: .data 0
+        ";
+        let program = assemble(source).unwrap();
+        for &(input, expected) in [(7, 999), (8, 1000), (9, 1001)].iter() {
+            let (mut machine, input_write, out_read) = Machine::new(&program);
+            input_write.send(input).unwrap();
+            assert_eq!(Ok(()), machine.execute(1000));
+            assert_eq!(vec![expected], out_read.try_iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_relative_and_offsets() {
+        let program = assemble("ARB #buf\nOUT @rb+1 ; second word\nOUT @rb-2\nADD @rb, buf+1, @rb\nhlt\nbuf: .data 7, 8, buf").unwrap();
+        assert_eq!(vec![109,11,204,1,204,-2,20201,0,12,0,99,7,8,11], program);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        let source: String = listing(&program).lines()
+            .map(|l| l[8..].to_string() + "\n")
+            .collect();
+        assert_eq!(program, assemble(&source).unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(AsmError::new(2, 3, "unknown mnemonic 'FOO'"), assemble("HLT\n  FOO 1").unwrap_err());
+        assert_eq!(AsmError::new(1, 13, "ADD cannot write to an immediate operand"), assemble("ADD 1, 2,   #3").unwrap_err());
+        assert_eq!(AsmError::new(1, 5, "undefined label 'nowhere'"), assemble("OUT nowhere").unwrap_err());
+        assert_eq!(AsmError::new(1, 1, "OUT takes 1 operand(s), found 2"), assemble("OUT 1, 2").unwrap_err());
+        assert_eq!(AsmError::new(2, 1, "duplicate label 'a'"), assemble("a: HLT\na: HLT").unwrap_err());
+        assert_eq!(AsmError::new(1, 8, "expected operand"), assemble("ADD 1, ,3").unwrap_err());
+        assert_eq!("1:5: undefined label 'x'", assemble("OUT x").unwrap_err().to_string());
+    }
+}
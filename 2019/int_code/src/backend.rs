@@ -0,0 +1,247 @@
+use crate::{Machine, Memory, Word, CHUNK_SIZE};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Backend {
+    // Hash map of fixed size chunks, for programs that touch huge addresses
+    Sparse,
+    // One growable vector, fastest for programs that stay near their own code
+    Flat,
+    // A flat prefix covering the program, with sparse chunks beyond it
+    Hybrid
+}
+
+impl Backend {
+    pub const ALL: [Backend; 3] = [Backend::Sparse, Backend::Flat, Backend::Hybrid];
+}
+
+// A non-zero word at an address the backend can't hold
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct BackendTooSmall {
+    pub backend: Backend,
+    pub address: Word
+}
+
+impl fmt::Display for BackendTooSmall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} memory can't hold the word at {}", self.backend, self.address)
+    }
+}
+impl StdError for BackendTooSmall {}
+
+// Largest address the flat backend will grow to, writes past it are access violations
+pub(crate) const FLAT_LIMIT: usize = 1 << 24;
+
+// Minimum size of the hybrid backend's flat prefix
+const HYBRID_PREFIX: usize = 8 * CHUNK_SIZE;
+
+pub(crate) trait Store {
+    fn get(&self, address: usize) -> Word;
+
+    // False if the backend cannot hold the address
+    fn set(&mut self, address: usize, value: Word) -> bool;
+
+    // Chunk aligned regions of memory that may be non-zero, in address order
+    fn segments(&self) -> Vec<(usize, &[Word])>;
+}
+
+#[derive(Clone)]
+pub(crate) struct Sparse {
+    chunks: HashMap<usize, Box<[Word; CHUNK_SIZE]>>
+}
+
+#[derive(Clone)]
+pub(crate) struct Flat {
+    words: Vec<Word>
+}
+
+#[derive(Clone)]
+pub(crate) struct Hybrid {
+    dense: Vec<Word>,
+    sparse: Sparse
+}
+
+impl Sparse {
+    #[allow(clippy::explicit_counter_loop)]
+    pub(crate) fn new(init: &[Word]) -> Sparse {
+        let mut m = Sparse{ chunks: HashMap::with_capacity((init.len() + CHUNK_SIZE - 1) / CHUNK_SIZE) };
+        let mut i = 0;
+        for chunk in init.chunks(CHUNK_SIZE) {
+            let mut tmp = Box::new([0; CHUNK_SIZE]);
+            tmp[..chunk.len()].copy_from_slice(chunk);
+            if m.chunks.insert(i, tmp).is_some() {
+                panic!("Overwrote memory on initialisation");
+            }
+            i += 1;
+        }
+        m
+    }
+}
+
+impl Store for Sparse {
+    fn get(&self, address: usize) -> Word {
+        match self.chunks.get(&(address / CHUNK_SIZE)) {
+            None => 0,
+            Some(chunk) => chunk[address % CHUNK_SIZE],
+        }
+    }
+
+    fn set(&mut self, address: usize, value: Word) -> bool {
+        self.chunks
+            .entry(address / CHUNK_SIZE)
+            .or_insert_with(|| Box::new([0; CHUNK_SIZE]))[address % CHUNK_SIZE] = value;
+        true
+    }
+
+    fn segments(&self) -> Vec<(usize, &[Word])> {
+        let mut segments = self.chunks.iter()
+            .map(|(&id, chunk)| (id * CHUNK_SIZE, &chunk[..]))
+            .collect::<Vec<_>>();
+        segments.sort_by_key(|&(address, _)| address);
+        segments
+    }
+}
+
+impl Flat {
+    pub(crate) fn new(init: &[Word]) -> Flat {
+        Flat{ words: init.to_vec() }
+    }
+}
+
+impl Store for Flat {
+    fn get(&self, address: usize) -> Word {
+        self.words.get(address).cloned().unwrap_or(0)
+    }
+
+    fn set(&mut self, address: usize, value: Word) -> bool {
+        if address >= self.words.len() {
+            if address >= FLAT_LIMIT {
+                return false;
+            }
+            self.words.resize(address + 1, 0);
+        }
+        self.words[address] = value;
+        true
+    }
+
+    fn segments(&self) -> Vec<(usize, &[Word])> {
+        self.words.chunks(CHUNK_SIZE)
+            .enumerate()
+            .map(|(i, chunk)| (i * CHUNK_SIZE, chunk))
+            .collect()
+    }
+}
+
+impl Hybrid {
+    pub(crate) fn new(init: &[Word]) -> Hybrid {
+        let len = ((init.len() + CHUNK_SIZE - 1) / CHUNK_SIZE).max(HYBRID_PREFIX / CHUNK_SIZE) * CHUNK_SIZE;
+        let mut dense = vec![0; len];
+        dense[..init.len()].copy_from_slice(init);
+        Hybrid{ dense, sparse: Sparse::new(&[]) }
+    }
+}
+
+impl Store for Hybrid {
+    fn get(&self, address: usize) -> Word {
+        match self.dense.get(address) {
+            Some(&w) => w,
+            None => self.sparse.get(address),
+        }
+    }
+
+    fn set(&mut self, address: usize, value: Word) -> bool {
+        match self.dense.get_mut(address) {
+            Some(w) => {
+                *w = value;
+                true
+            },
+            None => self.sparse.set(address, value),
+        }
+    }
+
+    fn segments(&self) -> Vec<(usize, &[Word])> {
+        let mut segments = self.dense.chunks(CHUNK_SIZE)
+            .enumerate()
+            .map(|(i, chunk)| (i * CHUNK_SIZE, chunk))
+            .collect::<Vec<_>>();
+        segments.extend(self.sparse.segments());
+        segments
+    }
+}
+
+impl<I, O> Machine<I, O> {
+    pub fn with_backend(memory: &[Word], backend: Backend, input: I, output: O) -> Machine<I, O> {
+        let mut machine = Machine::with_io(&[], input, output);
+        machine.memory = Memory::with_backend(memory, backend);
+        machine
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.memory.backend()
+    }
+
+    // Moves the current memory contents into a different backend, leaving memory as it was if any
+    // of it can't be held there
+    pub fn set_backend(&mut self, backend: Backend) -> Result<(), BackendTooSmall> {
+        if backend != self.backend() {
+            self.memory = self.memory.convert(backend)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExecuteError, parse_csv};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_distant_writes() {
+        // Stores its input at 50000 and 1 << 30, then outputs both
+        let memory = parse_csv("3,50000,1001,50000,0,1073741824,4,50000,4,1073741824,99").unwrap();
+        for &backend in Backend::ALL.iter() {
+            let mut machine = Machine::with_backend(&memory, backend, VecDeque::from(vec![3]), Vec::new());
+            assert_eq!(backend, machine.backend());
+            let result = machine.execute(100).map_err(|f| f.error);
+            if backend == Backend::Flat {
+                assert_eq!(Err(ExecuteError::MemoryAccessViolation(None, 1 << 30)), result);
+            }
+            else {
+                assert_eq!(Ok(()), result);
+                assert_eq!(&vec![3, 3], machine.output());
+            }
+        }
+    }
+
+    #[test]
+    fn test_convert() {
+        let memory = parse_csv("3,5000,99").unwrap();
+        let mut machine = Machine::with_io(&memory, VecDeque::from(vec![9]), Vec::new());
+        assert_eq!(Ok(()), machine.execute(10));
+        let saved = machine.save_state().to_bytes();
+        for &backend in Backend::ALL.iter().chain(Backend::ALL.iter()) {
+            machine.set_backend(backend).unwrap();
+            assert_eq!(backend, machine.backend());
+            assert_eq!(Ok(9), machine.memory.read_position(5000).map_err(|_| ()));
+            assert_eq!(Ok(3), machine.memory.read_position(0).map_err(|_| ()));
+            // Saves don't depend on how memory was stored
+            assert_eq!(saved, machine.save_state().to_bytes());
+        }
+        assert_eq!(Backend::Hybrid, machine.fork().0.backend());
+    }
+
+    #[test]
+    fn test_convert_too_far() {
+        let memory = parse_csv("3,16777216,99").unwrap();
+        let mut machine = Machine::with_backend(&memory, Backend::Sparse, VecDeque::from(vec![9]), Vec::new());
+        assert_eq!(Ok(()), machine.execute(10));
+        assert_eq!(Err(BackendTooSmall{ backend: Backend::Flat, address: FLAT_LIMIT as Word }), machine.set_backend(Backend::Flat));
+        assert_eq!(Backend::Sparse, machine.backend());
+        assert_eq!(Ok(9), machine.memory.read_position(FLAT_LIMIT as Word).map_err(|_| ()));
+        assert_eq!(Ok(()), machine.set_backend(Backend::Hybrid));
+        assert_eq!(Ok(9), machine.memory.read_position(FLAT_LIMIT as Word).map_err(|_| ()));
+    }
+}
@@ -0,0 +1,185 @@
+extern crate int_code;
+use int_code::*;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+const HELP: &str = "\
+s [n]             step n instructions (default 1)
+p [n]             step back n instructions (default 1)
+c [limit]         continue until a breakpoint, watchpoint, halt or error
+b <pc>            set breakpoint         d <pc>    delete breakpoint
+w <addr> [r|w|rw] set watchpoint         u <addr>  delete watchpoint
+i <word>...       queue input words
+r                 show pc, relative base and the current instruction
+x <addr> [count]  examine memory
+a <addr>          show the last instruction that wrote to addr
+l [count]         list instructions from pc
+m                 mark memory            diff      show changes since the mark
+dump [hex]        print all of memory    csv <file> save memory as a program
+q                 quit";
+
+// Instructions that can be stepped back over
+const HISTORY: usize = 1_000_000;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(p) => p,
+        None => {
+            eprintln!("usage: intcode-debug <program.csv>");
+            std::process::exit(2);
+        }
+    };
+    let source = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let memory = parse_csv(source.trim()).unwrap_or_else(|e| panic!("{}: {}", path, e));
+
+    let (mut machine, input, output) = Machine::new(&memory);
+    machine.set_history(History::new(HISTORY));
+    let mut debugger = Debugger::new(machine);
+    show_registers(&debugger);
+    let mut mark = debugger.dump();
+
+    let stdin = io::stdin();
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        if args.is_empty() {
+            continue;
+        }
+        let numbers = args[1..].iter().map(|a| a.parse::<Word>()).collect::<Result<Vec<_>, _>>();
+
+        match (args[0], numbers) {
+            ("q", _) => break,
+            ("h", _) | ("?", _) => println!("{}", HELP),
+            ("s", _) => {
+                let n = match count(args.get(1), 1u64) { Some(n) => n, None => continue };
+                for _ in 0..n {
+                    let stop = debugger.step();
+                    if stop != Ok(Stop::Stepped) {
+                        report(stop);
+                        break;
+                    }
+                }
+                show_registers(&debugger);
+            },
+            ("p", _) => {
+                let n = match count(args.get(1), 1u64) { Some(n) => n, None => continue };
+                for _ in 0..n {
+                    if !debugger.step_back() {
+                        println!("no more history");
+                        break;
+                    }
+                }
+                show_registers(&debugger);
+            },
+            ("a", Ok(ref n)) if n.len() == 1 => {
+                match debugger.history().and_then(|h| h.last_write(n[0])) {
+                    Some(Change{ step, pc, write: Some((_, old, new)), .. }) => println!("step {} at pc {}: {} -> {}", step, pc, old, new),
+                    _ => println!("no recorded write to {}", n[0]),
+                }
+            },
+            ("c", _) => {
+                let limit = match count(args.get(1), 1_000_000u32) { Some(n) => n, None => continue };
+                report(debugger.run(limit));
+                show_registers(&debugger);
+            },
+            ("b", Ok(ref n)) if n.len() == 1 => { debugger.add_breakpoint(n[0]); },
+            ("d", Ok(ref n)) if n.len() == 1 => { debugger.remove_breakpoint(n[0]); },
+            ("u", Ok(ref n)) if n.len() == 1 => { debugger.remove_watchpoint(n[0]); },
+            ("w", _) if args.len() >= 2 => {
+                let access = match args.get(2) {
+                    None | Some(&"rw") => Access::ReadWrite,
+                    Some(&"r") => Access::Read,
+                    Some(&"w") => Access::Write,
+                    Some(a) => { println!("unknown access '{}'", a); continue; }
+                };
+                match args[1].parse::<Word>() {
+                    Ok(address) => debugger.add_watchpoint(address, access),
+                    Err(e) => println!("{}", e),
+                }
+            },
+            ("i", Ok(n)) => {
+                for w in n {
+                    input.send(w).unwrap();
+                }
+            },
+            ("r", _) => show_registers(&debugger),
+            ("x", Ok(ref n)) if !n.is_empty() => {
+                let count = match count(args.get(2), 1u32) { Some(n) => n, None => continue };
+                for address in n[0]..n[0].saturating_add(count as Word) {
+                    match debugger.read(address) {
+                        Ok(w) => println!("{:>8}: {}", address, w),
+                        Err(e) => { println!("{}", e); break; }
+                    }
+                }
+            },
+            ("m", _) => mark = debugger.dump(),
+            ("diff", _) => {
+                for difference in mark.diff(&debugger.dump()) {
+                    println!("{}", difference);
+                }
+            },
+            ("dump", _) => {
+                let radix = if args.get(1) == Some(&"hex") { Radix::Hex } else { Radix::Decimal };
+                print!("{}", debugger.dump().columns(radix, 8));
+            },
+            ("csv", _) if args.len() == 2 => {
+                match debugger.dump().to_csv() {
+                    Ok(csv) => if let Err(e) = fs::write(args[1], csv) {
+                        println!("{}: {}", args[1], e);
+                    },
+                    Err(e) => println!("{}", e),
+                }
+            },
+            ("l", _) => {
+                let count = match count(args.get(1), 10usize) { Some(n) => n, None => continue };
+                for line in debugger.disassemble(count) {
+                    println!("{}", line);
+                }
+            },
+            _ => println!("unrecognised command, 'h' for help"),
+        }
+
+        for w in output.try_iter() {
+            println!("output: {}", w);
+        }
+    }
+}
+
+// An optional count or limit argument, which has to be an unsigned number that fits in T
+fn count<T: FromStr>(arg: Option<&&str>, default: T) -> Option<T> {
+    match arg {
+        None => Some(default),
+        Some(a) => {
+            let parsed = a.parse().ok();
+            if parsed.is_none() {
+                println!("'{}' isn't a valid count", a);
+            }
+            parsed
+        }
+    }
+}
+
+fn report(stop: Result<Stop, Fault>) {
+    match stop {
+        Ok(Stop::Stepped) => (),
+        Ok(Stop::Halted) => println!("halted"),
+        Ok(Stop::Breakpoint(pc)) => println!("breakpoint at {}", pc),
+        Ok(Stop::Watchpoint { pc, address, access }) => println!("watchpoint: {:?} of {} by instruction at {}", access, address, pc),
+        Err(e) => println!("stopped: {}", e),
+    }
+}
+
+fn show_registers(debugger: &Debugger) {
+    print!("pc={} rb={}", debugger.pc(), debugger.relative_base());
+    match debugger.current() {
+        Some(i) => println!("  {}", i.to_string().trim_start()),
+        None => println!("  <no instruction>"),
+    }
+}
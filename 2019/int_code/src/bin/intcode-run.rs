@@ -0,0 +1,189 @@
+extern crate int_code;
+use int_code::*;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+const USAGE: &str = "\
+usage: intcode-run [options] <program.csv>
+    --arithmetic <checked|wrapping|saturating|unbounded>
+                     what ADD and MUL do on overflow, checked by default
+    --ascii          read and write text instead of one integer per line
+    --diff           print each address the program changed, with its old and new value
+    --dump <dec|hex> print the final memory in columns
+    --input <file>   read input from a file before the terminal, may be repeated
+    --limit <n>      stop after n instructions
+    --memory         print the final memory as comma separated values
+    --profile        print execution counts, hot loops and unexecuted code to stderr";
+
+struct Options {
+    program: String,
+    arithmetic: Arithmetic,
+    ascii: bool,
+    diff: bool,
+    dump: Option<Radix>,
+    inputs: Vec<String>,
+    limit: u32,
+    memory: bool,
+    profile: bool
+}
+
+// Scripted input first, then a line at a time from stdin as the program asks for it
+struct Terminal {
+    ascii: bool,
+    queue: VecDeque<Word>
+}
+
+struct Screen {
+    ascii: bool
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        process::exit(2);
+    });
+    let memory = fs::read_to_string(&options.program)
+        .map_err(|e| e.to_string())
+        .and_then(|source| parse_csv(source.trim()).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("{}: {}", options.program, e);
+            process::exit(2);
+        });
+
+    let mut terminal = Terminal{ ascii: options.ascii, queue: VecDeque::new() };
+    for path in options.inputs.iter() {
+        let script = fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            process::exit(2);
+        });
+        for line in script.lines() {
+            if let Err(e) = terminal.push_line(line) {
+                eprintln!("{}: {}", path, e);
+                process::exit(2);
+            }
+        }
+    }
+
+    let mut machine = Machine::with_io(&memory, terminal, Screen{ ascii: options.ascii });
+    machine.set_arithmetic(options.arithmetic).unwrap();
+    if options.profile {
+        machine.set_profile(Profile::new());
+    }
+    let result = machine.execute(options.limit);
+    io::stdout().flush().unwrap();
+    if let Some(profile) = machine.profile() {
+        eprint!("{}", profile.summary(&memory, 10));
+    }
+    if options.memory {
+        match machine.dump().to_csv() {
+            Ok(csv) => println!("{}", csv),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    if let Some(radix) = options.dump {
+        print!("{}", machine.dump().columns(radix, 8));
+    }
+    if options.diff {
+        for difference in MemoryDump::new(&memory).diff(&machine.dump()) {
+            println!("{}", difference);
+        }
+    }
+    if let Err(fault) = result {
+        eprintln!("{}", fault);
+        process::exit(1);
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options{ program: String::new(), arithmetic: Arithmetic::Checked, ascii: false, diff: false, dump: None, inputs: Vec::new(), limit: u32::MAX, memory: false, profile: false };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ascii" => options.ascii = true,
+            "--diff" => options.diff = true,
+            "--memory" => options.memory = true,
+            "--profile" => options.profile = true,
+            "--input" => options.inputs.push(args.next().ok_or("--input needs a file")?),
+            "--limit" => {
+                let limit = args.next().ok_or("--limit needs a number")?;
+                options.limit = limit.parse().map_err(|e| format!("--limit {}: {}", limit, e))?;
+            },
+            "--dump" => {
+                let radix = args.next().ok_or("--dump needs a radix")?;
+                options.dump = match radix.as_str() {
+                    "dec" => Some(Radix::Decimal),
+                    "hex" => Some(Radix::Hex),
+                    _ => return Err(format!("unknown radix {}", radix)),
+                };
+            },
+            "--arithmetic" => {
+                let mode = args.next().ok_or("--arithmetic needs a mode")?;
+                options.arithmetic = match Arithmetic::from_name(&mode) {
+                    Some(a) if a.supported() => a,
+                    Some(a) => return Err(UnsupportedArithmetic(a).to_string()),
+                    None => return Err(format!("unknown arithmetic {}", mode)),
+                };
+            },
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if options.program.is_empty() => options.program = arg,
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    if options.program.is_empty() {
+        return Err("no program given".to_string());
+    }
+    Ok(options)
+}
+
+impl Terminal {
+    fn push_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if self.ascii {
+            self.queue.extend(line.bytes().map(Word::from));
+            self.queue.push_back(Word::from(b'\n'));
+        }
+        else {
+            for word in line.split(|c: char| c == ',' || c.is_whitespace()).filter(|w| !w.is_empty()) {
+                self.queue.push_back(word.parse().map_err(|e| format!("'{}': {}", word, e))?);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WordSource for Terminal {
+    fn read(&mut self) -> Result<Word, ReadError> {
+        while self.queue.is_empty() {
+            io::stdout().flush().map_err(|_| ReadError::Closed)?;
+            let mut line = String::new();
+            match io::stdin().lock().read_line(&mut line) {
+                Ok(0) | Err(_) => return Err(ReadError::Closed),
+                Ok(_) => {
+                    if let Err(e) = self.push_line(&line) {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+        self.queue.read()
+    }
+
+    fn queued(&self) -> Vec<Word> {
+        self.queue.queued()
+    }
+}
+
+impl WordSink for Screen {
+    fn write(&mut self, value: Word) -> Result<(), WriteError> {
+        let mut stdout = io::stdout();
+        let written = match value {
+            0..=127 if self.ascii => stdout.write_all(&[value as u8]),
+            _ => writeln!(stdout, "{}", value)
+        };
+        written.map_err(|_| WriteError)
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{Machine, Memory, Operation, Parameter, OutputParameter, DecodeError, Word};
+use crate::Operation::*;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Engine {
+    // Decodes every instruction from memory as it is executed
+    Interpreter,
+    // Keeps decoded instructions per address, dropping them when the program writes over them
+    Cached
+}
+
+// Instructions at addresses past this are decoded every time, so a jump to a huge address can't
+// allocate an equally huge cache
+const MAX_CACHED_PC: usize = 1 << 20;
+
+// Longest instruction, so a write can only affect instructions starting this many words before it
+pub(crate) const MAX_INSTRUCTION_LEN: Word = 4;
+
+// Operations are cached decoded against a relative base of 0 and rebased when fetched
+pub(crate) struct DecodeCache {
+    ops: Vec<Option<Operation>>
+}
+
+impl DecodeCache {
+    fn new() -> DecodeCache {
+        DecodeCache{ ops: Vec::new() }
+    }
+
+    pub(crate) fn decode(&mut self, memory: &Memory, pc: Word, relative_base: Word) -> Result<Operation, DecodeError> {
+        if pc < 0 || pc as usize >= MAX_CACHED_PC {
+            return Operation::decode(memory, pc, relative_base);
+        }
+        let index = pc as usize;
+        if let Some(Some(op)) = self.ops.get(index) {
+            return Ok(op.rebase(relative_base));
+        }
+        let op = Operation::decode(memory, pc, 0)?;
+        if self.ops.len() <= index {
+            self.ops.resize(index + 1, None);
+        }
+        self.ops[index] = Some(op);
+        Ok(op.rebase(relative_base))
+    }
+
+    pub(crate) fn invalidate(&mut self, address: Word) {
+        let first = address.saturating_sub(MAX_INSTRUCTION_LEN - 1).max(0);
+        for a in first..=address {
+            if let Some(op) = self.ops.get_mut(a as usize) {
+                *op = None;
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.ops.clear();
+    }
+}
+
+impl<I, O> Machine<I, O> {
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.cache = match engine {
+            Engine::Interpreter => None,
+            Engine::Cached => Some(DecodeCache::new()),
+        }
+    }
+
+    pub fn engine(&self) -> Engine {
+        match self.cache {
+            None => Engine::Interpreter,
+            Some(_) => Engine::Cached,
+        }
+    }
+}
+
+impl Operation {
+    fn rebase(self, relative_base: Word) -> Operation {
+        let p = |p: Parameter| match p {
+            Parameter::Position(Some(_), a) => Parameter::Position(Some(relative_base), a),
+            p => p
+        };
+        let out = |o: OutputParameter| match o {
+            OutputParameter(Some(_), a) => OutputParameter(Some(relative_base), a),
+            o => o
+        };
+        match self {
+            Add(a, b, o) => Add(p(a), p(b), out(o)),
+            Multiply(a, b, o) => Multiply(p(a), p(b), out(o)),
+            Input(o) => Input(out(o)),
+            Output(a) => Output(p(a)),
+            JumpIfTrue(a, b) => JumpIfTrue(p(a), p(b)),
+            JumpIfFalse(a, b) => JumpIfFalse(p(a), p(b)),
+            LessThan(a, b, o) => LessThan(p(a), p(b), out(o)),
+            Equals(a, b, o) => Equals(p(a), p(b), out(o)),
+            AddRelativeBase(a) => AddRelativeBase(p(a)),
+            Halt => Halt,
+            Custom(op) => Custom(op.map(p)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_csv;
+    use std::collections::VecDeque;
+
+    fn run(memory: &[Word], engine: Engine, input: Vec<Word>) -> Vec<Word> {
+        let mut machine = Machine::with_io(memory, VecDeque::from(input), Vec::new());
+        machine.set_engine(engine);
+        assert_eq!(Ok(()), machine.execute(10000));
+        machine.into_io().1
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        // Loops three times, each pass rewriting the immediate of its own OUT instruction
+        let memory = parse_csv("104,7,1001,1,1,1,1001,14,-1,14,1005,14,0,99,3").unwrap();
+        assert_eq!(vec![7, 8, 9], run(&memory, Engine::Cached, vec![]));
+        assert_eq!(run(&memory, Engine::Interpreter, vec![]), run(&memory, Engine::Cached, vec![]));
+    }
+
+    #[test]
+    fn test_relative_rebase() {
+        // The same cached OUT @rb instruction, run with three different relative bases
+        let memory = parse_csv("109,17,204,0,109,1,1001,19,-1,19,1005,19,2,99,0,0,0,5,6,3").unwrap();
+        assert_eq!(vec![5, 6, 1], run(&memory, Engine::Cached, vec![]));
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{Memory, Instruction, Mnemonic, Mode, Word};
+use crate::disasm::decode_at;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Exit {
+    // Runs straight into the block starting at this address
+    Next(usize),
+    // Jumps whatever the condition, because the condition is an immediate
+    Jump(Word),
+    Branch { taken: Word, next: usize },
+    // The target is read from memory, so only the fall through is known
+    Indirect { next: Option<usize> },
+    Halt,
+    // An invalid opcode, or an instruction running past the end of the program
+    Invalid
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+    pub exit: Exit
+}
+
+// Control flow graph of the code reachable from address 0, following only immediate jump targets.
+// Self-modifying code is analysed as originally loaded, and code only reached through indirect
+// jumps (such as function returns) is treated as data.
+#[derive(Clone, Debug)]
+pub struct Cfg {
+    blocks: BTreeMap<usize, Block>,
+    code: Vec<bool>
+}
+
+impl Block {
+    // First address after the block
+    pub fn end(&self) -> usize {
+        self.instructions.last().map_or(self.start, |i| i.address + i.size())
+    }
+
+    pub fn successors(&self) -> Vec<Word> {
+        match self.exit {
+            Exit::Next(next) | Exit::Indirect { next: Some(next) } => vec![next as Word],
+            Exit::Jump(target) => vec![target],
+            Exit::Branch { taken, next } => vec![taken, next as Word],
+            Exit::Indirect { next: None } | Exit::Halt | Exit::Invalid => vec![],
+        }
+    }
+}
+
+pub fn control_flow(program: &[Word]) -> Cfg {
+    let memory = Memory::new(program);
+    let decode = |address: usize| decode_at(&memory, address).filter(|i| address + i.size() <= program.len());
+
+    // Find every reachable instruction and the addresses that start blocks
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut pending = vec![0];
+    leaders.insert(0);
+    while let Some(address) = pending.pop() {
+        if address >= program.len() || instructions.contains_key(&address) {
+            continue;
+        }
+        let instruction = match decode(address) {
+            Some(i) => i,
+            None => continue
+        };
+        let next = address + instruction.size();
+        let (target, falls_through) = flow(&instruction);
+        if let Some(target) = target.filter(|&t| t >= 0) {
+            leaders.insert(target as usize);
+            pending.push(target as usize);
+        }
+        if falls_through {
+            if is_jump(&instruction) {
+                leaders.insert(next);
+            }
+            pending.push(next);
+        }
+        instructions.insert(address, instruction);
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut code = vec![false; program.len()];
+    for &start in leaders.iter().filter(|&&a| a < program.len()) {
+        let mut block = Block{ start, instructions: Vec::new(), exit: Exit::Invalid };
+        let mut address = start;
+        while let Some(instruction) = instructions.get(&address) {
+            let next = address + instruction.size();
+            for c in code[address..next].iter_mut() {
+                *c = true;
+            }
+            block.instructions.push(instruction.clone());
+            block.exit = exit(instruction, next);
+            if block.exit != Exit::Next(next) || leaders.contains(&next) {
+                break;
+            }
+            address = next;
+            block.exit = Exit::Invalid;
+        }
+        blocks.insert(start, block);
+    }
+    Cfg{ blocks, code }
+}
+
+// Immediate jump target, if any, and whether execution can continue with the next instruction
+fn flow(instruction: &Instruction) -> (Option<Word>, bool) {
+    match exit(instruction, instruction.address + instruction.size()) {
+        Exit::Next(_) => (None, true),
+        Exit::Jump(target) => (Some(target), false),
+        Exit::Branch { taken, .. } => (Some(taken), true),
+        Exit::Indirect { next } => (None, next.is_some()),
+        Exit::Halt | Exit::Invalid => (None, false),
+    }
+}
+
+fn is_jump(instruction: &Instruction) -> bool {
+    matches!(instruction.mnemonic, Mnemonic::Jt | Mnemonic::Jf)
+}
+
+fn exit(instruction: &Instruction, next: usize) -> Exit {
+    let (condition, target) = match instruction.mnemonic {
+        Mnemonic::Hlt => return Exit::Halt,
+        Mnemonic::Jt | Mnemonic::Jf => (instruction.operands[0], instruction.operands[1]),
+        _ => return Exit::Next(next)
+    };
+    // Some(true) when the jump is always taken, Some(false) when never
+    let constant = match condition.mode {
+        Mode::Immediate => Some((condition.value != 0) == (instruction.mnemonic == Mnemonic::Jt)),
+        _ => None
+    };
+    match (target.mode, constant) {
+        (_, Some(false)) => Exit::Next(next),
+        (Mode::Immediate, Some(true)) => Exit::Jump(target.value),
+        (Mode::Immediate, None) => Exit::Branch { taken: target.value, next },
+        (_, Some(true)) => Exit::Indirect { next: None },
+        (_, None) => Exit::Indirect { next: Some(next) },
+    }
+}
+
+impl Cfg {
+    pub fn blocks(&self) -> impl Iterator<Item=&Block> {
+        self.blocks.values()
+    }
+
+    pub fn block(&self, start: usize) -> Option<&Block> {
+        self.blocks.get(&start)
+    }
+
+    // Whether the word at this address is part of a reachable instruction
+    pub fn is_code(&self, address: usize) -> bool {
+        self.code.get(address).cloned().unwrap_or(false)
+    }
+
+    // Addresses of jump instructions whose target is read from memory
+    pub fn indirect_jumps(&self) -> Vec<usize> {
+        self.blocks()
+            .filter(|b| matches!(b.exit, Exit::Indirect { .. }))
+            .filter_map(|b| b.instructions.last().map(|i| i.address))
+            .collect()
+    }
+
+    // Graphviz source, one node per block. Blocks ending in an indirect jump are drawn in red with
+    // a dashed edge to an unknown target.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        dot.push_str("digraph intcode {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks() {
+            let label = block.instructions.iter()
+                .map(|i| i.to_string().trim_start().replace('\\', "\\\\").replace('"', "\\\"") + "\\l")
+                .collect::<String>();
+            let style = match block.exit {
+                Exit::Indirect { .. } => ", color=red",
+                Exit::Invalid => ", style=dashed",
+                _ => ""
+            };
+            writeln!(dot, "    b{} [label=\"{}\"{}];", block.start, label, style).unwrap();
+
+            let edges = match block.exit {
+                Exit::Branch { taken, next } => vec![(taken, " [label=\"taken\"]"), (next as Word, " [label=\"next\"]")],
+                _ => block.successors().into_iter().map(|s| (s, "")).collect()
+            };
+            for (to, attributes) in edges {
+                if to >= 0 && self.blocks.contains_key(&(to as usize)) {
+                    writeln!(dot, "    b{} -> b{}{};", block.start, to, attributes).unwrap();
+                }
+            }
+            if let Exit::Indirect { .. } = block.exit {
+                writeln!(dot, "    b{}_indirect [label=\"?\", shape=circle, color=red];", block.start).unwrap();
+                writeln!(dot, "    b{} -> b{}_indirect [style=dashed, color=red];", block.start, block.start).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, parse_csv};
+
+    #[test]
+    fn test_blocks() {
+        let program = assemble("
+                IN   n
+        loop:   OUT  n
+                ADD  n, #-1, n
+                JT   n, #loop
+                JF   #0, #end
+                .data 1, 2, 3
+        end:    HLT
+        n:      .data 0").unwrap();
+        let cfg = control_flow(&program);
+        let starts = cfg.blocks().map(|b| (b.start, b.exit)).collect::<Vec<_>>();
+        assert_eq!(vec![
+            (0, Exit::Next(2)),
+            (2, Exit::Branch { taken: 2, next: 11 }),
+            (11, Exit::Jump(17)),
+            (17, Exit::Halt)], starts);
+        assert_eq!(3, cfg.block(2).unwrap().instructions.len());
+        assert_eq!(14, cfg.block(11).unwrap().end());
+        assert_eq!(vec![true, false, false, false, true], [0, 14, 15, 16, 17].iter().map(|&a| cfg.is_code(a)).collect::<Vec<_>>());
+        assert!(!cfg.is_code(18));
+    }
+
+    #[test]
+    fn test_indirect_and_invalid() {
+        // Jumps through a stored return address, and a branch into an invalid opcode
+        let program = parse_csv("1105,1,7,99,0,0,0,5,10,11,0,42").unwrap();
+        let cfg = control_flow(&program);
+        assert_eq!(vec![7], cfg.indirect_jumps());
+        assert_eq!(Exit::Indirect { next: Some(10) }, cfg.block(7).unwrap().exit);
+        assert_eq!(Exit::Invalid, cfg.block(10).unwrap().exit);
+        assert!(cfg.block(3).is_none());
+        assert!(!cfg.is_code(3));
+
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph intcode {\n"));
+        assert!(dot.contains("    b0 [label=\"0  JT   #1, #7\\l\"];\n    b0 -> b7;\n"));
+        assert!(dot.contains("    b7 -> b10;\n    b7_indirect [label=\"?\", shape=circle, color=red];\n"));
+        assert!(dot.contains("    b10 [label=\"\", style=dashed];\n"));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{Machine, Memory, BigCells, Operation, advance, next_input, Parameter, OutputParameter, DecodeError, ExecuteError, StepError, StepResult, WordSource, WordSink, Word};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+
+// How an operand of a custom instruction is used, which decides the modes it can be given
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Slot {
+    // Position, immediate or relative
+    Read,
+    // Position or relative. An instruction writes at most one operand, and only through
+    // Call::write, so the debugger, history and decode cache see every change to memory.
+    Write
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum OpcodeError {
+    // One of the standard opcodes, 1 to 9 and 99
+    Reserved(Word),
+    // Not an opcode that fits in the two low digits of an instruction
+    OutOfRange(Word),
+    // More than two read operands or one written operand
+    TooManyOperands
+}
+
+impl fmt::Display for OpcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpcodeError::Reserved(opcode) => write!(f, "Opcode {} is reserved", opcode),
+            OpcodeError::OutOfRange(opcode) => write!(f, "Opcode {} is out of range", opcode),
+            OpcodeError::TooManyOperands => f.write_str("Too many operands"),
+        }
+    }
+}
+impl StdError for OpcodeError {}
+
+type Handler = Box<dyn FnMut(&mut Call) -> Result<(), ExecuteError> + Send>;
+
+struct Extension {
+    slots: Vec<Slot>,
+    handler: Handler
+}
+
+#[derive(Default)]
+pub(crate) struct Extensions {
+    opcodes: HashMap<Word, Extension>
+}
+
+// A decoded custom instruction. The written operand, if any, is kept as a position parameter.
+#[derive(Copy, Clone)]
+pub(crate) struct CustomOp {
+    pub(crate) opcode: Word,
+    args: [Option<Parameter>; 3],
+    write: Option<usize>
+}
+
+// What a handler sees of the machine while its instruction runs
+pub struct Call<'a> {
+    memory: &'a mut Memory,
+    big: &'a mut BigCells,
+    input: &'a mut dyn WordSource,
+    replay: &'a mut Vec<Word>,
+    blocking: bool,
+    waiting_input: &'a mut bool,
+    input_read: Option<Word>,
+    output: &'a mut dyn WordSink,
+    pc: Word,
+    relative_base: Word,
+    op: CustomOp,
+    jump: Option<Word>,
+    halt: bool
+}
+
+impl Extensions {
+    // Decodes an instruction the standard set didn't recognise, if its opcode has been registered
+    pub(crate) fn decode(&self, memory: &Memory, pc: Word, relative_base: Word, error: DecodeError) -> Result<Operation, DecodeError> {
+        let full_opcode = match error {
+            DecodeError::InvalidOpcode(op) if !self.opcodes.is_empty() => op,
+            _ => return Err(error)
+        };
+        let extension = match self.opcodes.get(&(full_opcode % 100)) {
+            Some(extension) => extension,
+            None => return Err(error)
+        };
+        let mut modes = full_opcode / 100;
+        let mut op = CustomOp{ opcode: full_opcode % 100, args: [None; 3], write: None };
+        for (i, &slot) in extension.slots.iter().enumerate() {
+            let value = memory.operand(pc, 1 + i as Word)?;
+            op.args[i] = Some(match (modes % 10, slot) {
+                (0, _) => Parameter::Position(None, value),
+                (1, Slot::Read) => Parameter::Immediate(value),
+                (2, _) => Parameter::Position(Some(relative_base), value),
+                _ => return Err(DecodeError::InvalidOpcode(full_opcode))
+            });
+            if slot == Slot::Write {
+                op.write = Some(i);
+            }
+            modes /= 10;
+        }
+        if modes != 0 {
+            return Err(DecodeError::InvalidOpcode(full_opcode));
+        }
+        Ok(Operation::Custom(op))
+    }
+}
+
+impl CustomOp {
+    pub(crate) fn size(&self) -> Word {
+        1 + self.args.iter().flatten().count() as Word
+    }
+
+    pub(crate) fn operands(&self) -> impl Iterator<Item=Parameter> + '_ {
+        self.args.iter().flatten().cloned()
+    }
+
+    pub(crate) fn inputs(&self) -> [Option<Parameter>; 2] {
+        let mut inputs = [None; 2];
+        let reads = self.args.iter().enumerate().filter(|&(i, _)| Some(i) != self.write);
+        for (input, (_, &arg)) in inputs.iter_mut().zip(reads) {
+            *input = arg;
+        }
+        inputs
+    }
+
+    pub(crate) fn output(&self) -> Option<OutputParameter> {
+        match self.write.and_then(|i| self.args[i]) {
+            Some(Parameter::Position(relative_base, addr)) => Some(OutputParameter(relative_base, addr)),
+            _ => None
+        }
+    }
+
+    pub(crate) fn map(self, f: impl Fn(Parameter) -> Parameter) -> CustomOp {
+        let mut args = self.args;
+        for arg in args.iter_mut() {
+            *arg = arg.map(&f);
+        }
+        CustomOp{ args, ..self }
+    }
+}
+
+impl<'a> Call<'a> {
+    pub fn opcode(&self) -> Word {
+        self.op.opcode
+    }
+
+    pub fn pc(&self) -> Word {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> Word {
+        self.relative_base
+    }
+
+    // The value of an operand. For the written operand that is what's currently at its address.
+    // Panics if the instruction has no such operand.
+    pub fn arg(&self, index: usize) -> Result<Word, ExecuteError> {
+        let parameter = self.op.args.get(index).cloned().flatten().expect("no such operand");
+        Ok(self.memory.read(parameter)?)
+    }
+
+    // Stores a value at the written operand's address. Panics if the instruction has no Write slot.
+    pub fn write(&mut self, value: Word) -> Result<(), ExecuteError> {
+        let out = self.op.output().expect("no Write operand");
+        self.memory.write(out, value)?;
+        // As for any other write, an exact value kept at the address no longer applies
+        if let Some(address) = out.address() {
+            self.big.forget(address);
+        }
+        Ok(())
+    }
+
+    pub fn read(&self, address: Word) -> Result<Word, ExecuteError> {
+        Ok(self.memory.read_position(address)?)
+    }
+
+    // Reads a word as IN would, so the history, trace and a blocking run all see it. With no input
+    // waiting the instruction stops and is run again from the start once there is, so a handler
+    // should read before doing anything else. Panics if called twice in one instruction.
+    pub fn read_input(&mut self) -> Result<Word, ExecuteError> {
+        assert!(self.input_read.is_none(), "a custom instruction can read at most one input word");
+        let value = next_input(self.replay, self.input, self.blocking, self.waiting_input)?;
+        self.input_read = Some(value);
+        Ok(value)
+    }
+
+    pub fn output(&mut self) -> &mut dyn WordSink {
+        self.output
+    }
+
+    // Continues at `target` instead of the next instruction
+    pub fn jump(&mut self, target: Word) {
+        self.jump = Some(target);
+    }
+
+    // Stops the machine as HLT would, leaving the pc on this instruction
+    pub fn halt(&mut self) {
+        self.halt = true;
+    }
+}
+
+impl<I, O> Machine<I, O> {
+    // Adds an instruction with a two digit opcode and the given operands, replacing any earlier
+    // registration of the same opcode. Custom instructions aren't known to the disassembler,
+    // assembler or control flow analysis, and are dropped by a fork or a resumed snapshot.
+    pub fn register<F>(&mut self, opcode: Word, slots: &[Slot], handler: F) -> Result<(), OpcodeError>
+        where F: FnMut(&mut Call) -> Result<(), ExecuteError> + Send + 'static
+    {
+        if !(1..=99).contains(&opcode) {
+            return Err(OpcodeError::OutOfRange(opcode));
+        }
+        if opcode <= 9 || opcode == 99 {
+            return Err(OpcodeError::Reserved(opcode));
+        }
+        let writes = slots.iter().filter(|&&s| s == Slot::Write).count();
+        if writes > 1 || slots.len() - writes > 2 {
+            return Err(OpcodeError::TooManyOperands);
+        }
+        let extension = Extension{ slots: slots.to_vec(), handler: Box::new(handler) };
+        self.extensions.opcodes.insert(opcode, extension);
+        Ok(())
+    }
+
+    pub fn unregister(&mut self, opcode: Word) -> bool {
+        self.extensions.opcodes.remove(&opcode).is_some()
+    }
+}
+
+impl<I: WordSource, O: WordSink> Machine<I, O> {
+    pub(crate) fn perform_custom(&mut self, op: CustomOp) -> Result<StepResult, StepError> {
+        let extension = match self.extensions.opcodes.get_mut(&op.opcode) {
+            Some(extension) => extension,
+            None => return Err(StepError::Handler(ExecuteError::UnrecognisedOpcode(op.opcode)))
+        };
+        let mut call = Call{
+            memory: &mut self.memory,
+            big: &mut self.big,
+            input: &mut self.input,
+            replay: &mut self.replay,
+            blocking: self.blocking,
+            waiting_input: &mut self.waiting_input,
+            input_read: None,
+            output: &mut self.output,
+            pc: self.pc,
+            relative_base: self.relative_base,
+            op,
+            jump: None,
+            halt: false
+        };
+        let result = (extension.handler)(&mut call);
+        let (jump, halt) = (call.jump, call.halt);
+        self.handler_input = call.input_read;
+        result.map_err(|e| match e {
+            ExecuteError::InputRequired => StepError::InputRequired,
+            ExecuteError::NoProgress => StepError::NoProgress,
+            e => StepError::Handler(e)
+        })?;
+        self.waiting_input = false;
+        if halt {
+            return Ok(StepResult::Halt);
+        }
+        self.pc = match jump {
+            Some(target) => target,
+            None => advance(self.pc, op.size())?
+        };
+        Ok(StepResult::Executed)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_csv, Engine, History, IoEvent, Mnemonic, Tracer};
+    use std::collections::VecDeque;
+
+    // MOD a, b, out and a debug print that outputs its operand twice
+    fn extended(program: &str) -> Machine<VecDeque<Word>, Vec<Word>> {
+        let mut machine = Machine::with_io(&parse_csv(program).unwrap(), VecDeque::new(), Vec::new());
+        machine.register(20, &[Slot::Read, Slot::Read, Slot::Write], |call: &mut Call| {
+            let (a, b) = (call.arg(0)?, call.arg(1)?);
+            call.write(a.checked_rem(b).ok_or(ExecuteError::ArithmeticOverflow)?)
+        }).unwrap();
+        machine.register(42, &[Slot::Read], |call: &mut Call| {
+            let value = call.arg(0)?;
+            for _ in 0..2 {
+                call.output().write(value).map_err(|_| ExecuteError::OutputError)?;
+            }
+            Ok(())
+        }).unwrap();
+        machine
+    }
+
+    #[test]
+    fn test_custom_instructions() {
+        // 17 % 5 into address 13, printed by the custom print and OUT, then 2 % -3 from a relative
+        // operand into address 14
+        let program = "1120,17,5,13,42,13,4,13,21220,13,-3,14,99,0,0";
+        for &engine in [Engine::Interpreter, Engine::Cached].iter() {
+            let mut machine = extended(program);
+            machine.set_engine(engine);
+            machine.set_tracer(Tracer::ring(10));
+            assert_eq!(Ok(()), machine.execute(100));
+            assert_eq!(&vec![2, 2, 2], machine.output());
+            assert_eq!(Ok(2), machine.memory.read_position(14).map_err(|_| ()));
+            let trace = machine.tracer().unwrap().entries().map(|e| e.instruction.mnemonic).collect::<Vec<_>>();
+            assert_eq!(vec![Mnemonic::Ext(20), Mnemonic::Ext(42), Mnemonic::Out, Mnemonic::Ext(20), Mnemonic::Hlt], trace);
+            let first = machine.tracer().unwrap().entries().next().unwrap().instruction.to_string();
+            assert_eq!("     0  EXT20 #17, #5, 13", first);
+        }
+
+        // An immediate written operand, or an unregistered opcode, doesn't decode
+        assert_eq!(Err(ExecuteError::UnrecognisedOpcode(11120)), extended("11120,1,1,1,99").execute(10).map_err(|f| f.error));
+        assert_eq!(Err(ExecuteError::UnrecognisedOpcode(21)), extended("21,1,1,1,99").execute(10).map_err(|f| f.error));
+        assert_eq!(Err(ExecuteError::ArithmeticOverflow), extended("1120,1,0,0,99").execute(10).map_err(|f| f.error));
+
+        let mut machine = extended("1120,1,1,1,99");
+        assert!(machine.unregister(20));
+        assert_eq!(Err(ExecuteError::UnrecognisedOpcode(1120)), machine.execute(10).map_err(|f| f.error));
+    }
+
+    #[test]
+    fn test_input() {
+        // Doubles an input word into address 5, then outputs it
+        let mut machine = Machine::with_io(&parse_csv("60,5,4,5,99,0").unwrap(), VecDeque::new(), Vec::new());
+        machine.register(60, &[Slot::Write], |call: &mut Call| {
+            let value = call.read_input()?;
+            call.write(value * 2)
+        }).unwrap();
+        machine.set_history(History::new(10));
+        machine.set_tracer(Tracer::ring(10));
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(10).map_err(|f| f.error));
+        assert_eq!(Err(ExecuteError::NoProgress), machine.execute(10).map_err(|f| f.error));
+        assert!(machine.tracer().unwrap().entries().next().is_none());
+
+        machine.input_mut().push_back(21);
+        assert_eq!(Ok(StepResult::Executed), machine.step().map_err(|f| f.error));
+        assert_eq!(Some(IoEvent::Input(21)), machine.tracer().unwrap().entries().next().unwrap().io);
+        assert_eq!(Some(IoEvent::Input(21)), machine.history().unwrap().changes().next().unwrap().io);
+
+        // Stepping back gives the input back to be read again
+        assert!(machine.step_back());
+        assert_eq!(Ok(()), machine.execute(10));
+        assert_eq!(&vec![42], machine.output());
+    }
+
+    #[test]
+    fn test_control_and_registration() {
+        // JMP #4 skips the first OUT, then STOP halts
+        let mut machine = Machine::with_io(&parse_csv("130,4,104,1,104,2,31,104,3").unwrap(), VecDeque::new(), Vec::new());
+        machine.register(30, &[Slot::Read], |call: &mut Call| {
+            let target = call.arg(0)?;
+            call.jump(target);
+            Ok(())
+        }).unwrap();
+        machine.register(31, &[], |call: &mut Call| {
+            call.halt();
+            Ok(())
+        }).unwrap();
+        assert_eq!(Ok(()), machine.execute(10));
+        assert_eq!((&vec![2], 6, 2), (machine.output(), machine.pc, machine.steps()));
+
+        let handler = |_: &mut Call| Ok(());
+        assert_eq!(Err(OpcodeError::Reserved(7)), machine.register(7, &[], handler));
+        assert_eq!(Err(OpcodeError::OutOfRange(100)), machine.register(100, &[], handler));
+        assert_eq!(Err(OpcodeError::TooManyOperands), machine.register(50, &[Slot::Write, Slot::Write], handler));
+        assert_eq!(Err(OpcodeError::TooManyOperands), machine.register(50, &[Slot::Read; 3], handler));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{Machine, Memory, History, WordSource, WordSink, Receiver, Sender, Operation, Parameter, OutputParameter, StepResult, ExecuteError, Fault, Instruction, Line, MemoryDump, Word, disassemble};
+use crate::cache::MAX_INSTRUCTION_LEN;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Hash)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Stop {
+    Stepped,
+    Halted,
+    Breakpoint(Word),
+    // pc of the instruction that touched the watched address
+    Watchpoint { pc: Word, address: Word, access: Access }
+}
+
+pub struct Debugger<I = Receiver<Word>, O = Sender<Word>> {
+    machine: Machine<I, O>,
+    breakpoints: BTreeSet<Word>,
+    watchpoints: BTreeMap<Word, Access>
+}
+
+impl Access {
+    fn matches(self, other: Access) -> bool {
+        self == Access::ReadWrite || self == other
+    }
+}
+
+impl<I: WordSource, O: WordSink> Debugger<I, O> {
+    pub fn new(machine: Machine<I, O>) -> Debugger<I, O> {
+        Debugger{ machine, breakpoints: BTreeSet::new(), watchpoints: BTreeMap::new() }
+    }
+
+    pub fn into_inner(self) -> Machine<I, O> {
+        self.machine
+    }
+
+    pub fn pc(&self) -> Word {
+        self.machine.pc
+    }
+
+    pub fn relative_base(&self) -> Word {
+        self.machine.relative_base
+    }
+
+    pub fn read(&self, address: Word) -> Result<Word, ExecuteError> {
+        self.machine.peek(address)
+    }
+
+    pub fn dump(&self) -> MemoryDump {
+        self.machine.dump()
+    }
+
+    // The instruction at the current pc, or None if it does not decode
+    pub fn current(&self) -> Option<Instruction> {
+        match self.disassemble(1).into_iter().next() {
+            Some(Line::Instruction(i)) => Some(i),
+            _ => None
+        }
+    }
+
+    // Up to `count` lines of disassembly starting at the current pc
+    pub fn disassemble(&self, count: usize) -> Vec<Line> {
+        // One instruction at a time, so a huge count only reads as far as it needs to
+        let mut lines = Vec::new();
+        let mut pc = self.machine.pc;
+        while lines.len() < count {
+            let words = (0..MAX_INSTRUCTION_LEN)
+                .map_while(|i| pc.checked_add(i).and_then(|a| self.read(a).ok()))
+                .collect::<Vec<_>>();
+            let (line, size) = match disassemble(&words).into_iter().next() {
+                Some(Line::Instruction(mut i)) => {
+                    i.address += pc as usize;
+                    let size = i.size();
+                    (Line::Instruction(i), size)
+                },
+                Some(Line::Data { value, .. }) => (Line::Data { address: pc as usize, value }, 1),
+                None => break
+            };
+            lines.push(line);
+            match pc.checked_add(size as Word) {
+                Some(next) => pc = next,
+                None => break
+            }
+        }
+        lines
+    }
+
+    pub fn add_breakpoint(&mut self, pc: Word) -> bool {
+        self.breakpoints.insert(pc)
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: Word) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item=Word> + '_ {
+        self.breakpoints.iter().cloned()
+    }
+
+    pub fn add_watchpoint(&mut self, address: Word, access: Access) {
+        self.watchpoints.insert(address, access);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: Word) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item=(Word, Access)> + '_ {
+        self.watchpoints.iter().map(|(&a, &w)| (a, w))
+    }
+
+    // Undoes the last instruction, if the machine is keeping a history
+    pub fn step_back(&mut self) -> bool {
+        self.machine.step_back()
+    }
+
+    pub fn history(&self) -> Option<&History> {
+        self.machine.history()
+    }
+
+    pub fn step(&mut self) -> Result<Stop, Fault> {
+        let pc = self.machine.pc;
+        let watched = if self.watchpoints.is_empty() {
+            None
+        }
+        else {
+            let (memory, relative_base) = (&self.machine.memory, self.machine.relative_base);
+            Operation::decode(memory, pc, relative_base)
+                .or_else(|e| self.machine.extensions.decode(memory, pc, relative_base, e))
+                .ok()
+                .and_then(|op| self.watched_access(&op, memory))
+        };
+        match self.machine.step()? {
+            StepResult::Halt => Ok(Stop::Halted),
+            StepResult::Executed => match watched {
+                Some((address, access)) => Ok(Stop::Watchpoint { pc, address, access }),
+                None => Ok(Stop::Stepped)
+            }
+        }
+    }
+
+    // Runs until a breakpoint or watchpoint is hit, or the machine halts or fails. A breakpoint on the
+    // current pc does not stop the first instruction, so repeated calls make progress.
+    pub fn run(&mut self, limit: u32) -> Result<Stop, Fault> {
+        for i in 0..limit {
+            if i != 0 && self.breakpoints.contains(&self.machine.pc) {
+                return Ok(Stop::Breakpoint(self.machine.pc));
+            }
+            match self.step()? {
+                Stop::Stepped => (),
+                stop => return Ok(stop)
+            }
+        }
+        if limit != 0 && self.breakpoints.contains(&self.machine.pc) {
+            return Ok(Stop::Breakpoint(self.machine.pc));
+        }
+        Err(self.machine.fault(ExecuteError::ExecutionLimitReached))
+    }
+
+    fn watched_access(&self, op: &Operation, memory: &Memory) -> Option<(Word, Access)> {
+        let (reads, write) = op.accesses(memory);
+        let hit = |address: Word, access: Access| {
+            match self.watchpoints.get(&address) {
+                Some(w) if w.matches(access) => Some((address, access)),
+                _ => None
+            }
+        };
+        write.and_then(|a| hit(a, Access::Write))
+            .or_else(|| reads.iter().flatten().filter_map(|&a| hit(a, Access::Read)).next())
+    }
+}
+
+impl Operation {
+    // Absolute addresses of the operands read and written by this operation when run on `memory`
+    pub(crate) fn accesses(&self, memory: &Memory) -> ([Option<Word>; 2], Option<Word>) {
+        let [a, b] = self.reads(memory);
+        ([a.and_then(|p| p.address()), b.and_then(|p| p.address())], self.output().and_then(|p| p.address()))
+    }
+}
+
+impl Parameter {
+    pub(crate) fn address(&self) -> Option<Word> {
+        match *self {
+            Parameter::Immediate(_) => None,
+            Parameter::Position(rb, addr) => rb.unwrap_or(0).checked_add(addr),
+        }
+    }
+}
+
+impl OutputParameter {
+    pub(crate) fn address(&self) -> Option<Word> {
+        self.0.unwrap_or(0).checked_add(self.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_csv, Mnemonic};
+
+    #[test]
+    fn test_breakpoints() {
+        let memory = parse_csv("1101,1,2,9,1101,3,4,10,99").unwrap();
+        let (machine, _, _) = Machine::new(&memory);
+        let mut debugger = Debugger::new(machine);
+        debugger.add_breakpoint(4);
+        debugger.add_breakpoint(8);
+
+        assert_eq!(Ok(Stop::Breakpoint(4)), debugger.run(100));
+        assert_eq!(Ok(3), debugger.read(9));
+        assert_eq!(Some(Mnemonic::Add), debugger.current().map(|i| i.mnemonic));
+        assert_eq!(Ok(Stop::Breakpoint(8)), debugger.run(100));
+        assert_eq!(Ok(7), debugger.read(10));
+        assert_eq!(Ok(Stop::Halted), debugger.run(100));
+    }
+
+    #[test]
+    fn test_breakpoint_at_limit() {
+        // The last instruction the limit allows lands on the breakpoint
+        let memory = parse_csv("1101,1,2,9,1101,3,4,10,99").unwrap();
+        let (machine, _, _) = Machine::new(&memory);
+        let mut debugger = Debugger::new(machine);
+        debugger.add_breakpoint(8);
+
+        assert_eq!(Ok(Stop::Breakpoint(8)), debugger.run(2));
+        assert_eq!(Err(ExecuteError::ExecutionLimitReached), debugger.run(0).map_err(|f| f.error));
+        assert_eq!(Ok(Stop::Halted), debugger.run(1));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        // Jumps to a 1 written at the last address, where nothing past it can be read
+        let memory = parse_csv("1101,1,0,9223372036854775807,1105,1,9223372036854775807").unwrap();
+        let (machine, _, _) = Machine::new(&memory);
+        let mut debugger = Debugger::new(machine);
+        assert_eq!(3, debugger.disassemble(3).len());
+        assert_eq!(Some(Mnemonic::Jt), debugger.disassemble(2).get(1).and_then(|l| match l { Line::Instruction(i) => Some(i.mnemonic), _ => None }));
+        debugger.step().unwrap();
+        debugger.step().unwrap();
+        assert_eq!(vec![Line::Data { address: Word::MAX as usize, value: 1 }], debugger.disassemble(usize::MAX));
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let memory = parse_csv("109,20,21201,0,5,0,22101,0,0,1,99").unwrap();
+        let (machine, _, _) = Machine::new(&memory);
+        let mut debugger = Debugger::new(machine);
+        debugger.add_watchpoint(20, Access::Write);
+        debugger.add_watchpoint(21, Access::Write);
+
+        assert_eq!(Ok(Stop::Stepped), debugger.step());
+        assert_eq!(20, debugger.relative_base());
+        assert_eq!(Ok(Stop::Watchpoint { pc: 2, address: 20, access: Access::Write }), debugger.run(100));
+        assert_eq!(Ok(5), debugger.read(20));
+        assert_eq!(Ok(Stop::Watchpoint { pc: 6, address: 21, access: Access::Write }), debugger.run(100));
+        assert_eq!(Ok(5), debugger.read(21));
+
+        debugger.add_watchpoint(0, Access::Read);
+        assert_eq!(Ok(Stop::Halted), debugger.run(100));
+    }
+
+    #[test]
+    fn test_read_watchpoint() {
+        let memory = parse_csv("4,5,4,6,99,7,8").unwrap();
+        let (machine, _, out) = Machine::new(&memory);
+        let mut debugger = Debugger::new(machine);
+        debugger.add_watchpoint(6, Access::ReadWrite);
+
+        assert_eq!(Ok(Stop::Watchpoint { pc: 2, address: 6, access: Access::Read }), debugger.run(100));
+        assert_eq!(vec![7, 8], out.try_iter().collect::<Vec<_>>());
+        assert_eq!(Err(ExecuteError::MemoryAccessViolation(None, -1)), debugger.read(-1));
+    }
+
+    #[test]
+    fn test_untaken_jump_watchpoint() {
+        // The JF at 0 is taken, reading its target at 8, but the JT at 3 is not and never reads 9
+        let memory = parse_csv("6,7,8,5,7,9,99,0,3,0").unwrap();
+        let (machine, _, _) = Machine::new(&memory);
+        let mut debugger = Debugger::new(machine);
+        debugger.add_watchpoint(8, Access::Read);
+        debugger.add_watchpoint(9, Access::Read);
+
+        assert_eq!(Ok(Stop::Watchpoint { pc: 0, address: 8, access: Access::Read }), debugger.run(100));
+        assert_eq!(Ok(Stop::Halted), debugger.run(100));
+    }
+}
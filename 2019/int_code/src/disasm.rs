@@ -0,0 +1,259 @@
+use crate::{Memory, Operation, Parameter, OutputParameter, Word};
+use crate::Operation::*;
+use std::fmt;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Hash)]
+pub enum Mnemonic {
+    Add,
+    Mul,
+    In,
+    Out,
+    Jt,
+    Jf,
+    Lt,
+    Eq,
+    Arb,
+    Hlt,
+    // A custom instruction, with its two digit opcode
+    Ext(Word)
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Hash)]
+pub struct Operand {
+    pub mode: Mode,
+    pub value: Word
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Instruction {
+    pub address: usize,
+    pub opcode: Word,
+    pub mnemonic: Mnemonic,
+    pub operands: Vec<Operand>
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Line {
+    Instruction(Instruction),
+    Data { address: usize, value: Word }
+}
+
+impl Mnemonic {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mnemonic::Add => "ADD",
+            Mnemonic::Mul => "MUL",
+            Mnemonic::In => "IN",
+            Mnemonic::Out => "OUT",
+            Mnemonic::Jt => "JT",
+            Mnemonic::Jf => "JF",
+            Mnemonic::Lt => "LT",
+            Mnemonic::Eq => "EQ",
+            Mnemonic::Arb => "ARB",
+            Mnemonic::Hlt => "HLT",
+            Mnemonic::Ext(_) => "EXT",
+        }
+    }
+}
+
+impl Instruction {
+    pub fn size(&self) -> usize {
+        self.operands.len() + 1
+    }
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Instruction(i) => i.address,
+            Line::Data { address, .. } => *address,
+        }
+    }
+}
+
+// Linear sweep over the program: anything that decodes to a complete instruction is shown as one,
+// everything else (invalid opcodes, truncated instructions) is emitted one word at a time as DATA
+pub fn disassemble(program: &[Word]) -> Vec<Line> {
+    let memory = Memory::new(program);
+    let mut lines = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        match decode_at(&memory, address) {
+            Some(instruction) if address + instruction.size() <= program.len() => {
+                address += instruction.size();
+                lines.push(Line::Instruction(instruction));
+            },
+            _ => {
+                lines.push(Line::Data { address, value: program[address] });
+                address += 1;
+            }
+        }
+    }
+    lines
+}
+
+pub fn listing(program: &[Word]) -> String {
+    let mut out = String::new();
+    for line in disassemble(program) {
+        out.push_str(&line.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+pub(crate) fn decode_at(memory: &Memory, address: usize) -> Option<Instruction> {
+    let pc = address as Word;
+    // Relative operands are only distinguished by being Some(_), so a zero base keeps their raw offset
+    let op = Operation::decode(memory, pc, 0).ok()?;
+    let opcode = memory.read_position(pc).ok()?;
+    Some(Instruction{ address, opcode, mnemonic: op.mnemonic(), operands: op.operands() })
+}
+
+impl Operation {
+    pub(crate) fn mnemonic(&self) -> Mnemonic {
+        match self {
+            Add(..) => Mnemonic::Add,
+            Multiply(..) => Mnemonic::Mul,
+            Input(..) => Mnemonic::In,
+            Output(..) => Mnemonic::Out,
+            JumpIfTrue(..) => Mnemonic::Jt,
+            JumpIfFalse(..) => Mnemonic::Jf,
+            LessThan(..) => Mnemonic::Lt,
+            Equals(..) => Mnemonic::Eq,
+            AddRelativeBase(..) => Mnemonic::Arb,
+            Halt => Mnemonic::Hlt,
+            Custom(op) => Mnemonic::Ext(op.opcode),
+        }
+    }
+
+    pub(crate) fn operands(&self) -> Vec<Operand> {
+        match self {
+            Add(a, b, out) | Multiply(a, b, out) | LessThan(a, b, out) | Equals(a, b, out) =>
+                vec![a.operand(), b.operand(), out.operand()],
+            Input(out) => vec![out.operand()],
+            Output(a) | AddRelativeBase(a) => vec![a.operand()],
+            JumpIfTrue(a, b) | JumpIfFalse(a, b) => vec![a.operand(), b.operand()],
+            Halt => vec![],
+            Custom(op) => op.operands().map(|p| p.operand()).collect(),
+        }
+    }
+}
+
+impl Parameter {
+    fn operand(&self) -> Operand {
+        match *self {
+            Parameter::Immediate(value) => Operand{ mode: Mode::Immediate, value },
+            Parameter::Position(None, value) => Operand{ mode: Mode::Position, value },
+            Parameter::Position(Some(_), value) => Operand{ mode: Mode::Relative, value },
+        }
+    }
+}
+
+impl OutputParameter {
+    fn operand(&self) -> Operand {
+        match *self {
+            OutputParameter(None, value) => Operand{ mode: Mode::Position, value },
+            OutputParameter(Some(_), value) => Operand{ mode: Mode::Relative, value },
+        }
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mnemonic::Ext(opcode) => write!(f, "{}{:02}", self.name(), opcode),
+            _ => f.write_str(self.name())
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "{}", self.value),
+            Mode::Immediate => write!(f, "#{}", self.value),
+            Mode::Relative if self.value == 0 => f.write_str("@rb"),
+            Mode::Relative if self.value < 0 => write!(f, "@rb{}", self.value),
+            Mode::Relative => write!(f, "@rb+{}", self.value),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic = self.mnemonic.to_string();
+        write!(f, "{:>6}  {}", self.address, mnemonic)?;
+        for (i, operand) in self.operands.iter().enumerate() {
+            if i == 0 {
+                write!(f, "{:width$} {}", "", operand, width = 4usize.saturating_sub(mnemonic.len()))?;
+            }
+            else {
+                write!(f, ", {}", operand)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Instruction(i) => i.fmt(f),
+            Line::Data { address, value } => write!(f, "{:>6}  DATA {}", address, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_csv;
+
+    #[test]
+    fn test_modes() {
+        let mem = parse_csv("1002,4,3,4,33").unwrap();
+        let lines = disassemble(&mem);
+        assert_eq!(Line::Instruction(Instruction{
+            address: 0,
+            opcode: 1002,
+            mnemonic: Mnemonic::Mul,
+            operands: vec![
+                Operand{ mode: Mode::Position, value: 4 },
+                Operand{ mode: Mode::Immediate, value: 3 },
+                Operand{ mode: Mode::Position, value: 4 },
+            ]
+        }), lines[0]);
+        assert_eq!(Line::Data{ address: 4, value: 33 }, lines[1]);
+    }
+
+    #[test]
+    fn test_listing() {
+        let mem = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        let expected = concat!(
+            "     0  ARB  #1\n",
+            "     2  OUT  @rb-1\n",
+            "     4  ADD  100, #1, 100\n",
+            "     8  EQ   100, #16, 101\n",
+            "    12  JF   101, #0\n",
+            "    15  HLT\n");
+        assert_eq!(expected, listing(&mem));
+    }
+
+    #[test]
+    fn test_truncated_and_invalid() {
+        // 98 is not an opcode, the others need more words than remain
+        let mem = vec![98, 22201, 1, 1101];
+        let lines = disassemble(&mem);
+        assert_eq!(4, lines.len());
+        assert_eq!(Line::Data{ address: 0, value: 98 }, lines[0]);
+        assert_eq!(Line::Data{ address: 3, value: 1101 }, lines[3]);
+        assert!(lines.iter().all(|l| matches!(l, Line::Data{..})));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{Machine, Snapshot, Word};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::fmt;
+
+// Largest address to_csv will write out to, as every word before it is written too
+const CSV_LIMIT: Word = 1 << 24;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Radix {
+    Decimal,
+    Hex
+}
+
+// The non-zero words of memory at some point, to print, export or compare with another
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct MemoryDump {
+    words: BTreeMap<Word, Word>
+}
+
+// An address whose value differs between two dumps
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Difference {
+    pub address: Word,
+    pub old: Word,
+    pub new: Word
+}
+
+// Memory reaching too far to write out every word, with the last non-zero address
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct CsvTooLarge(pub Word);
+
+impl MemoryDump {
+    pub fn new(memory: &[Word]) -> MemoryDump {
+        MemoryDump::from_segments(vec![(0, memory)])
+    }
+
+    fn from_segments<'a>(segments: impl IntoIterator<Item=(usize, &'a [Word])>) -> MemoryDump {
+        let words = segments.into_iter()
+            .flat_map(|(address, words)| words.iter().enumerate().map(move |(i, &w)| ((address + i) as Word, w)))
+            .filter(|&(_, w)| w != 0)
+            .collect();
+        MemoryDump{ words }
+    }
+
+    pub fn get(&self, address: Word) -> Word {
+        self.words.get(&address).cloned().unwrap_or(0)
+    }
+
+    // Rows of words, each labelled with its first address. Runs of rows that are all zero are
+    // left out and marked with a single '*', like hexdump does, so huge addresses stay readable.
+    pub fn columns(&self, radix: Radix, columns: usize) -> String {
+        let columns = Word::try_from(columns).unwrap_or(Word::MAX).max(1);
+        let mut rows = self.words.keys().map(|&a| a / columns).collect::<Vec<_>>();
+        rows.dedup();
+        let format = |w: Word| match radix {
+            Radix::Decimal => w.to_string(),
+            Radix::Hex if w < 0 => format!("-{:x}", w.unsigned_abs()),
+            Radix::Hex => format!("{:x}", w),
+        };
+        let address_width = rows.last().map_or(1, |&r| format(r * columns).len());
+        let width = self.words.values().map(|&w| format(w).len()).max().unwrap_or(1);
+
+        let mut text = String::new();
+        let mut next = 0;
+        for row in rows {
+            if row != next {
+                text.push_str("*\n");
+            }
+            // The last row can end past Word::MAX, where there are no more addresses
+            let start = row * columns;
+            let cells = (start..=start.saturating_add(columns - 1))
+                .map(|a| format!("{:>1$}", format(self.get(a)), width))
+                .collect::<Vec<_>>();
+            text.push_str(&format!("{:>1$}: {2}\n", format(start), address_width, cells.join(" ")));
+            next = row.saturating_add(1);
+        }
+        text
+    }
+
+    // Every word from address 0 to the last non-zero one, as parse_csv reads. Empty memory is a
+    // single zero, as parse_csv can't read an empty program.
+    pub fn to_csv(&self) -> Result<String, CsvTooLarge> {
+        match self.words.keys().next_back() {
+            Some(&last) if last > CSV_LIMIT => Err(CsvTooLarge(last)),
+            Some(&last) => Ok((0..=last).map(|a| self.get(a).to_string()).collect::<Vec<_>>().join(",")),
+            None => Ok("0".to_string())
+        }
+    }
+
+    // The addresses that changed between this dump and a later one, in address order
+    pub fn diff(&self, after: &MemoryDump) -> Vec<Difference> {
+        let mut addresses = self.words.keys().chain(after.words.keys()).cloned().collect::<Vec<_>>();
+        addresses.sort_unstable();
+        addresses.dedup();
+        addresses.into_iter()
+            .map(|address| Difference{ address, old: self.get(address), new: after.get(address) })
+            .filter(|d| d.old != d.new)
+            .collect()
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.address, self.old, self.new)
+    }
+}
+
+impl fmt::Display for CsvTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Memory up to address {} is too large to write as comma separated values", self.0)
+    }
+}
+impl StdError for CsvTooLarge {}
+
+impl<I, O> Machine<I, O> {
+    pub fn dump(&self) -> MemoryDump {
+        MemoryDump::from_segments(self.memory.segments())
+    }
+}
+
+impl Snapshot {
+    pub fn dump(&self) -> MemoryDump {
+        MemoryDump::from_segments(self.memory.segments())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_csv, Backend};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_columns() {
+        let mut dump = MemoryDump::new(&[1, 2, -300, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!("0:    1    2 -300    0\n4:    4    0    0    0\n", dump.columns(Radix::Decimal, 4));
+        dump.words.insert(1 << 20, 255);
+        assert_eq!("     0:    1    2 -12c    0\n     4:    4    0    0    0\n*\n100000:   ff    0    0    0\n",
+                   dump.columns(Radix::Hex, 4));
+        assert_eq!("", MemoryDump::default().columns(Radix::Hex, 4));
+
+        // The last row stops at the last address
+        let mut dump = MemoryDump::default();
+        dump.words.insert(Word::MAX, 7);
+        assert_eq!("*\n9223372036854775804: 0 0 0 7\n", dump.columns(Radix::Decimal, 4));
+        assert_eq!("*\n9223372036854775807: 7\n", dump.columns(Radix::Decimal, 1));
+    }
+
+    #[test]
+    fn test_csv() {
+        let program = parse_csv("1101,3,-4,9,99,0,0,0").unwrap();
+        assert_eq!(Ok("1101,3,-4,9,99".to_string()), MemoryDump::new(&program).to_csv());
+        let mut machine = Machine::with_backend(&program, Backend::Hybrid, VecDeque::new(), Vec::new());
+        machine.execute(10).unwrap();
+        let csv = machine.dump().to_csv().unwrap();
+        assert_eq!("1101,3,-4,9,99,0,0,0,0,-1", csv);
+        assert_eq!(machine.dump(), MemoryDump::new(&parse_csv(&csv).unwrap()));
+        assert_eq!(Ok(vec![0]), parse_csv(&MemoryDump::default().to_csv().unwrap()));
+
+        // A write far away would need every word before it
+        let mut dump = MemoryDump::new(&program);
+        dump.words.insert(1 << 40, 1);
+        assert_eq!(Err(CsvTooLarge(1 << 40)), dump.to_csv());
+    }
+
+    #[test]
+    fn test_diff() {
+        // Doubles the word at 10 and zeroes the one at 11
+        let program = parse_csv("1002,10,2,10,1102,0,1,11,99,0,21,5").unwrap();
+        let mut machine = Machine::with_io(&program, VecDeque::new(), Vec::new());
+        let before = machine.snapshot().dump();
+        machine.execute(10).unwrap();
+        let changes = before.diff(&machine.dump());
+        assert_eq!(vec![Difference{ address: 10, old: 21, new: 42 }, Difference{ address: 11, old: 5, new: 0 }], changes);
+        assert_eq!("10: 21 -> 42", changes[0].to_string());
+        assert_eq!(Vec::<Difference>::new(), before.diff(&before));
+    }
+}
@@ -0,0 +1,350 @@
+use crate::{Machine, Backend, Engine, ExecuteError, Fault, Word};
+use crate::backend::FLAT_LIMIT;
+use std::collections::{BTreeMap, VecDeque};
+
+// Xorshift generator, so a failing case can be reproduced from its seed
+pub struct XorShift(u64);
+
+// A program with its input and instruction limit
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct FuzzCase {
+    pub program: Vec<Word>,
+    pub input: Vec<Word>,
+    pub limit: u32
+}
+
+// How a run finished, as compared between the machine and the reference interpreter
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Outcome {
+    pub result: Result<(), ExecuteError>,
+    pub pc: Word,
+    pub steps: u64,
+    pub output: Vec<Word>,
+    // Every non-zero word
+    pub memory: BTreeMap<usize, Word>
+}
+
+// Values that sit on the edges of what the interpreter handles
+const EDGES: [Word; 8] = [Word::MIN, Word::MIN + 1, Word::MAX, Word::MAX - 1, 1 << 62, -(1 << 62), 1 << 32, 34915192];
+
+const OPCODES: [Word; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+
+// Instructions with their sizes, to place so they end at or just before the last address
+const LAST: [(Word, Word); 5] = [(104, 2), (109, 2), (3, 2), (1106, 3), (1101, 4)];
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        XorShift(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // Uniform enough in 0..n for n much smaller than 2^64
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+impl FuzzCase {
+    // A program made mostly of valid instructions whose operands point into or just past it, with
+    // a sprinkling of bad modes, negative and huge addresses and values that overflow
+    pub fn random(rng: &mut XorShift) -> FuzzCase {
+        let instructions = 1 + rng.below(30);
+        let size = instructions * 4;
+        let mut program = Vec::new();
+        for _ in 0..instructions {
+            if rng.below(20) == 0 {
+                program.push(operand(rng, size));
+                continue;
+            }
+            // Writes an instruction near the end of memory and jumps to it
+            if rng.below(40) == 0 {
+                let (instruction, len) = rng.pick(&LAST);
+                let at = Word::MAX - (len - 1) - rng.below(2) as Word;
+                program.extend_from_slice(&[1101, instruction, 0, at, 1105, 1, at]);
+                continue;
+            }
+            let opcode = rng.pick(&OPCODES);
+            let operands = match opcode {
+                1 | 2 | 7 | 8 => 3,
+                5 | 6 => 2,
+                3 | 4 | 9 => 1,
+                _ => 0
+            };
+            let mut modes = 0;
+            for i in 0..operands {
+                let mode = match rng.below(20) {
+                    0 => 3 + rng.below(7) as Word,
+                    _ if i == 2 || opcode == 3 => rng.pick(&[0, 0, 2]),
+                    _ => rng.below(3) as Word,
+                };
+                modes += mode * 10i64.pow(i);
+            }
+            program.push(modes * 100 + opcode);
+            for _ in 0..operands {
+                program.push(operand(rng, size));
+            }
+        }
+        let input = (0..rng.below(6)).map(|_| operand(rng, size)).collect();
+        FuzzCase{ program, input, limit: rng.below(300) as u32 }
+    }
+
+    // Reads a case from fuzzer data. The first byte sets the limit and the second how many words
+    // of input there are. Then each word is a byte from -100 to 99, a tag followed by a 16 bit
+    // value, or a tag followed by a full 64 bit value.
+    pub fn from_bytes(data: &[u8]) -> FuzzCase {
+        let mut bytes = data.iter().cloned();
+        let limit = bytes.next().map_or(0, |b| b as u32 * 4);
+        let inputs = bytes.next().map_or(0, |b| (b % 8) as usize);
+        let mut words = Vec::new();
+        while let Some(tag) = bytes.next() {
+            let mut take = |n: usize| {
+                let mut buffer = [0; 8];
+                for b in buffer.iter_mut().take(n) {
+                    *b = bytes.next().unwrap_or(0);
+                }
+                buffer
+            };
+            words.push(match tag {
+                0..=199 => tag as Word - 100,
+                200..=249 => { let b = take(2); i16::from_le_bytes([b[0], b[1]]) as Word },
+                _ => Word::from_le_bytes(take(8)),
+            });
+        }
+        let program = words.split_off(inputs.min(words.len()));
+        FuzzCase{ program, input: words, limit }
+    }
+
+    // Runs the case on every engine and backend and checks each against the reference
+    // interpreter, describing the first difference
+    pub fn check(&self) -> Result<(), String> {
+        let expected = reference(&self.program, &self.input, self.limit);
+        for &engine in [Engine::Interpreter, Engine::Cached].iter() {
+            for &backend in Backend::ALL.iter() {
+                let mut machine = Machine::with_backend(&self.program, backend, VecDeque::from(self.input.clone()), Vec::new());
+                machine.set_engine(engine);
+                let result = machine.execute(self.limit);
+                let outcome = outcome(machine, result);
+                check_invariants(&outcome, self.limit)
+                    .map_err(|e| format!("{:?}/{:?}: {}", engine, backend, e))?;
+                // The flat backend refuses addresses past its limit, where the others carry on
+                if backend == Backend::Flat && refused(&outcome.result) {
+                    continue;
+                }
+                if outcome != expected {
+                    return Err(format!("{:?}/{:?}: expected {:?}, got {:?}", engine, backend, expected, outcome));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn operand(rng: &mut XorShift, size: u64) -> Word {
+    match rng.below(20) {
+        0..=11 => rng.below(size + 8) as Word,
+        12..=14 => rng.below(21) as Word - 10,
+        15..=17 => rng.pick(&EDGES),
+        18 => rng.pick(&OPCODES) + 100 * rng.below(300) as Word,
+        _ => rng.next_u64() as Word,
+    }
+}
+
+fn outcome<I>(machine: Machine<I, Vec<Word>>, result: Result<(), Fault>) -> Outcome {
+    let memory = machine.memory.segments().into_iter()
+        .flat_map(|(address, words)| words.iter().enumerate().map(move |(i, &w)| (address + i, w)))
+        .filter(|&(_, w)| w != 0)
+        .collect();
+    Outcome{
+        result: result.map_err(|f| f.error),
+        pc: machine.pc,
+        steps: machine.steps,
+        output: machine.output,
+        memory
+    }
+}
+
+fn check_invariants(outcome: &Outcome, limit: u32) -> Result<(), String> {
+    match outcome.result {
+        Ok(()) |
+        Err(ExecuteError::InputRequired) |
+        Err(ExecuteError::ArithmeticOverflow) |
+        Err(ExecuteError::UnrecognisedOpcode(_)) |
+        Err(ExecuteError::MemoryAccessViolation(_, _)) => (),
+        Err(ExecuteError::ExecutionLimitReached) if outcome.steps == limit as u64 => (),
+        Err(e) => return Err(format!("unexpected {} after {} steps", e, outcome.steps)),
+    }
+    if outcome.steps > limit as u64 {
+        return Err(format!("ran {} steps with a limit of {}", outcome.steps, limit));
+    }
+    Ok(())
+}
+
+fn refused(result: &Result<(), ExecuteError>) -> bool {
+    matches!(*result, Err(ExecuteError::MemoryAccessViolation(_, address)) if address >= FLAT_LIMIT as Word)
+}
+
+// A deliberately plain interpreter over a map of non-zero words, written separately from Machine
+// to compare against it
+pub fn reference(program: &[Word], input: &[Word], limit: u32) -> Outcome {
+    let mut memory = program.iter().cloned().enumerate().filter(|&(_, w)| w != 0).collect::<BTreeMap<_, _>>();
+    let mut input = input.iter().cloned();
+    let mut output = Vec::new();
+    let (mut pc, mut relative_base, mut steps) = (0, 0, 0);
+    let result = loop {
+        if steps == limit as u64 {
+            break Err(ExecuteError::ExecutionLimitReached);
+        }
+        match reference_step(&mut memory, &mut pc, &mut relative_base, &mut input, &mut output) {
+            Ok(true) => steps += 1,
+            Ok(false) => break Ok(()),
+            Err(e) => break Err(e)
+        }
+    };
+    Outcome{ result, pc, steps, output, memory }
+}
+
+type Memory = BTreeMap<usize, Word>;
+
+// The address base + offset, where the base is the relative base or the pc of an instruction
+fn at(base: Option<Word>, offset: Word) -> Result<usize, ExecuteError> {
+    match base.unwrap_or(0).checked_add(offset) {
+        Some(a) if a >= 0 => Ok(a as usize),
+        Some(a) => Err(ExecuteError::MemoryAccessViolation(base, a)),
+        None => Err(ExecuteError::MemoryAccessViolation(base, offset)),
+    }
+}
+
+fn reference_step(memory: &mut Memory, pc: &mut Word, relative_base: &mut Word, input: &mut dyn Iterator<Item=Word>, output: &mut Vec<Word>) -> Result<bool, ExecuteError> {
+    let word = |memory: &Memory, address: Word| -> Result<Word, ExecuteError> {
+        Ok(*memory.get(&at(None, address)?).unwrap_or(&0))
+    };
+
+    let instruction = word(memory, *pc)?;
+    let (opcode, modes) = (instruction % 100, instruction / 100);
+    // Operand words are all fetched before the modes are checked
+    let count = match opcode {
+        1 | 2 | 7 | 8 => 3,
+        5 | 6 => 2,
+        3 | 4 | 9 => 1,
+        99 if instruction == 99 => return Ok(false),
+        _ => return Err(ExecuteError::UnrecognisedOpcode(instruction))
+    };
+    let mut operands = [0; 3];
+    for (i, operand) in operands.iter_mut().enumerate().take(count) {
+        let address = at(Some(*pc), 1 + i as Word)?;
+        *operand = *memory.get(&address).unwrap_or(&0);
+    }
+    let invalid = ExecuteError::UnrecognisedOpcode(instruction);
+    // Mode of each operand, with the digits past the last one having to be zero, except for jumps
+    let mode = |i: u32| modes / 10i64.pow(i) % 10;
+    let digits_ok = match opcode {
+        1 | 2 | 7 | 8 => mode(0) <= 2 && mode(1) <= 2 && (modes / 100 == 0 || modes / 100 == 2),
+        3 => modes == 0 || modes == 2,
+        4 | 9 => (0..=2).contains(&modes),
+        _ => mode(0) <= 2 && mode(1) <= 2,
+    };
+    if !digits_ok {
+        return Err(invalid);
+    }
+    let rb = *relative_base;
+    let read = |memory: &Memory, i: usize| -> Result<Word, ExecuteError> {
+        match mode(i as u32) {
+            1 => Ok(operands[i]),
+            m => {
+                let address = at(if m == 2 { Some(rb) } else { None }, operands[i])?;
+                Ok(*memory.get(&address).unwrap_or(&0))
+            }
+        }
+    };
+    let write = |memory: &mut Memory, i: usize, value: Word| -> Result<(), ExecuteError> {
+        let mode = if opcode == 3 { modes } else { modes / 100 };
+        let address = at(if mode == 2 { Some(rb) } else { None }, operands[i])?;
+        if value == 0 {
+            memory.remove(&address);
+        }
+        else {
+            memory.insert(address, value);
+        }
+        Ok(())
+    };
+
+    match opcode {
+        1 | 2 | 7 | 8 => {
+            let (a, b) = (read(memory, 0)?, read(memory, 1)?);
+            let value = match opcode {
+                1 => a.checked_add(b).ok_or(ExecuteError::ArithmeticOverflow)?,
+                2 => a.checked_mul(b).ok_or(ExecuteError::ArithmeticOverflow)?,
+                7 => (a < b) as Word,
+                _ => (a == b) as Word,
+            };
+            write(memory, 2, value)?;
+            *pc = at(Some(*pc), 4)? as Word;
+        },
+        3 => {
+            let value = input.next().ok_or(ExecuteError::InputRequired)?;
+            write(memory, 0, value)?;
+            *pc = at(Some(*pc), 2)? as Word;
+        },
+        4 => {
+            output.push(read(memory, 0)?);
+            *pc = at(Some(*pc), 2)? as Word;
+        },
+        5 | 6 => {
+            if (read(memory, 0)? != 0) == (opcode == 5) {
+                *pc = read(memory, 1)?;
+            }
+            else {
+                *pc = at(Some(*pc), 3)? as Word;
+            }
+        },
+        _ => {
+            *relative_base = rb.checked_add(read(memory, 0)?).ok_or(ExecuteError::ArithmeticOverflow)?;
+            *pc = at(Some(*pc), 2)? as Word;
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_programs() {
+        let mut rng = XorShift::new(0x1c0de);
+        for i in 0..2000 {
+            let case = FuzzCase::random(&mut rng);
+            if let Err(e) = case.check() {
+                panic!("case {}: {}\n{:?}", i, e, case);
+            }
+        }
+    }
+
+    #[test]
+    fn test_edges() {
+        // Negative and overflowing addresses, for the pc, operands and relative base
+        for program in ["1,-1,0,0,99", "109,-5,204,4,99", "109,9223372036854775807,22201,1,1,1,99",
+                        "1105,1,-3", "1105,1,9223372036854775807", "2,0,0,20000000,99",
+                        "1101,9223372036854775807,1,0,99", "3,0,3,0", "10005,0,0",
+                        "1101,1,0,9223372036854775807,1105,1,9223372036854775807",
+                        "1101,104,0,9223372036854775806,1105,1,9223372036854775806"].iter() {
+            let program = crate::parse_csv(program).unwrap();
+            for &limit in [0, 1, 5].iter() {
+                let case = FuzzCase{ program: program.clone(), input: vec![7], limit };
+                assert_eq!(Ok(()), case.check(), "{:?}", case);
+            }
+        }
+        assert_eq!(FuzzCase{ program: vec![1101, -100, 99, 0], input: vec![3], limit: 8 },
+                   FuzzCase::from_bytes(&[2, 1, 103, 200, 0x4d, 0x04, 0, 199, 100]));
+    }
+}
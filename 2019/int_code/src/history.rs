@@ -0,0 +1,197 @@
+use crate::{Machine, Operation, OutputParameter, IoEvent, Mnemonic, Word};
+use std::collections::VecDeque;
+
+// What one executed instruction changed, enough to undo it
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Change {
+    // Number of instructions executed before this one
+    pub step: u64,
+    pub pc: Word,
+    pub relative_base: Word,
+    // Address, old value and new value of any memory write
+    pub write: Option<(Word, Word, Word)>,
+    pub io: Option<IoEvent>,
+    waiting_input: bool
+}
+
+// Undo log of the most recent `capacity` instructions
+pub struct History {
+    changes: VecDeque<Change>,
+    capacity: usize
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History{ changes: VecDeque::new(), capacity }
+    }
+
+    // Recorded changes, oldest first
+    pub fn changes(&self) -> impl Iterator<Item=&Change> {
+        self.changes.iter()
+    }
+
+    // The most recent instruction that wrote to `address`
+    pub fn last_write(&self, address: Word) -> Option<&Change> {
+        self.changes.iter().rev().find(|c| matches!(c.write, Some((a, _, _)) if a == address))
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Earliest instruction count the machine can be rewound to
+    pub fn earliest(&self) -> Option<u64> {
+        self.changes.front().map(|c| c.step)
+    }
+
+    fn push(&mut self, change: Change) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.changes.len() == self.capacity {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+    }
+}
+
+impl<I, O> Machine<I, O> {
+    pub fn set_history(&mut self, history: History) {
+        self.history = Some(history);
+    }
+
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    pub fn take_history(&mut self) -> Option<History> {
+        self.history.take()
+    }
+
+    // The state before the instruction is performed, with the old value of anything it writes
+    pub(crate) fn begin_change(&self, op: &Operation) -> Change {
+        let write = op.output()
+            .and_then(|out| out.address())
+            .and_then(|a| self.memory.read_position(a).ok().map(|old| (a, old, old)));
+        let io = match *op {
+            Operation::Output(a) => self.memory.read(a).ok().map(IoEvent::Output),
+            _ => None
+        };
+        Change{ step: self.steps, pc: self.pc, relative_base: self.relative_base, write, io, waiting_input: self.waiting_input }
+    }
+
+    pub(crate) fn finish_change(&mut self, mut change: Change, op: &Operation) {
+        if let Some((address, _, ref mut new)) = change.write {
+            *new = self.memory.read_position(address).unwrap_or(0);
+        }
+        match op.mnemonic() {
+            Mnemonic::In => change.io = change.write.map(|(_, _, new)| IoEvent::Input(new)),
+            Mnemonic::Ext(_) => change.io = self.handler_input.map(IoEvent::Input),
+            _ => ()
+        }
+        if let Some(history) = self.history.as_mut() {
+            history.push(change);
+        }
+    }
+
+    // Undoes the last executed instruction. Input it consumed is read again when the machine runs
+    // on, but output cannot be recalled and will be written a second time. Returns false when
+    // there is no recorded history left.
+    pub fn step_back(&mut self) -> bool {
+        let change = match self.history.as_mut().and_then(|h| h.changes.pop_back()) {
+            Some(change) => change,
+            None => return false
+        };
+        if let Some((address, old, _)) = change.write {
+            // An exact value too big for a Word comes back as its saturated shadow
+            self.memory.write(OutputParameter(None, address), old).ok();
+            self.big.forget(address);
+            if let Some(cache) = self.cache.as_mut() {
+                cache.invalidate(address);
+            }
+        }
+        if let Some(IoEvent::Input(value)) = change.io {
+            self.replay.push(value);
+        }
+        self.pc = change.pc;
+        self.relative_base = change.relative_base;
+        self.waiting_input = change.waiting_input;
+        self.steps = change.step;
+        true
+    }
+
+    // Steps back until `steps` instructions have been executed. Returns false, having gone back as
+    // far as possible, if the history doesn't reach that far.
+    pub fn rewind(&mut self, steps: u64) -> bool {
+        while self.steps > steps {
+            if !self.step_back() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, parse_csv, ExecuteError, Engine, StepResult};
+
+    #[test]
+    fn test_step_back() {
+        // Adds up inputs until it reads a zero
+        let program = assemble("
+        loop:   IN   x
+                ADD  sum, x, sum
+                JT   x, #loop
+                OUT  sum
+                HLT
+        x:      .data 0
+        sum:    .data 0").unwrap();
+        let (mut machine, input, output) = Machine::new(&program);
+        machine.set_history(History::new(100));
+        for &w in [3, 4, 0].iter() {
+            input.send(w).unwrap();
+        }
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(Ok(7), output.try_recv());
+        assert_eq!(10, machine.steps());
+
+        let history = machine.history().unwrap();
+        assert_eq!(Some(IoEvent::Input(4)), history.changes().nth(3).unwrap().io);
+        let last = history.last_write(12).unwrap();
+        assert_eq!((6, Some((12, 4, 0))), (last.step, last.write));
+
+        // Back to just after the first ADD, then the 4 and 0 are read again
+        assert!(machine.rewind(2));
+        assert_eq!((6, 3), (machine.pc, machine.memory.read_position(13).ok().unwrap()));
+        machine.memory.write(OutputParameter(None, 13), 10).ok();
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(Ok(14), output.try_recv());
+
+        assert!(machine.rewind(0));
+        assert_eq!((0, 0), (machine.pc, machine.memory.read_position(13).ok().unwrap()));
+        assert!(!machine.step_back());
+    }
+
+    #[test]
+    fn test_cache_and_capacity() {
+        // Loops forever, changing its own OUT operand from 7 to 8 on the first pass
+        let program = parse_csv("104,7,1101,8,0,1,1105,1,0").unwrap();
+        let mut machine = Machine::with_io(&program, VecDeque::new(), Vec::new());
+        machine.set_engine(Engine::Cached);
+        machine.set_history(History::new(10));
+        assert_eq!(Err(ExecuteError::ExecutionLimitReached), machine.execute(4).map_err(|f| f.error));
+        assert_eq!(&vec![7, 8], machine.output());
+        assert!(machine.rewind(0));
+        assert_eq!(Ok(StepResult::Executed), machine.step());
+        assert_eq!(&vec![7, 8, 7], machine.output());
+
+        let mut machine = Machine::with_io(&program, VecDeque::new(), Vec::new());
+        machine.set_history(History::new(2));
+        assert_eq!(Err(ExecuteError::ExecutionLimitReached), machine.execute(4).map_err(|f| f.error));
+        assert_eq!(Some(2), machine.history().unwrap().earliest());
+        assert!(!machine.rewind(0));
+        assert_eq!((2, 6), (machine.steps(), machine.pc));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{Machine, ExecuteError, Word};
+use std::ops::Range;
+
+impl<I, O> Machine<I, O> {
+    pub fn pc(&self) -> Word {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> Word {
+        self.relative_base
+    }
+
+    pub fn peek(&self, address: Word) -> Result<Word, ExecuteError> {
+        Ok(self.memory.read_position(address)?)
+    }
+
+    // Every word in a range of addresses, failing if any is out of bounds
+    pub fn peek_range(&self, addresses: Range<Word>) -> Result<Vec<Word>, ExecuteError> {
+        if addresses.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.memory.read_position(addresses.start)?;
+        Ok(addresses.map(|a| self.memory.get(a as usize)).collect())
+    }
+
+    // Writes a word from outside the program, as a patch of one cell
+    pub fn poke(&mut self, address: Word, value: Word) -> Result<(), ExecuteError> {
+        self.patch(&[(address, value)])
+    }
+
+    // The start address and contents of each chunk of memory holding a non-zero word, in address
+    // order. Chunks are 1024 words long, except perhaps the last with the flat backend.
+    pub fn chunks(&self) -> impl Iterator<Item=(Word, &[Word])> {
+        self.memory.segments().into_iter()
+            .filter(|(_, words)| words.iter().any(|&w| w != 0))
+            .map(|(address, words)| (address as Word, words))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Machine, Backend, Engine, ExecuteError, History, Word, parse_csv};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_peek_and_poke() {
+        let program = parse_csv("109,7,21101,2,3,0,99").unwrap();
+        for &backend in Backend::ALL.iter() {
+            let mut machine = Machine::with_backend(&program, backend, VecDeque::new(), Vec::new());
+            assert_eq!(Ok(()), machine.execute(10));
+            assert_eq!((6, 7), (machine.pc(), machine.relative_base()));
+            assert_eq!(Ok(5), machine.peek(7));
+            assert_eq!(Ok(0), machine.peek(1 << 40));
+            assert_eq!(Err(ExecuteError::MemoryAccessViolation(None, -1)), machine.peek(-1));
+            assert_eq!(Ok(vec![99, 5, 0]), machine.peek_range(6..9));
+            assert_eq!(Ok(vec![]), machine.peek_range(-5..-5));
+            assert_eq!(Err(ExecuteError::MemoryAccessViolation(None, -2)), machine.peek_range(-2..3));
+
+            assert_eq!(Ok(()), machine.poke(5000, -1));
+            assert_eq!(Err(ExecuteError::MemoryAccessViolation(None, Word::MIN)), machine.poke(Word::MIN, 1));
+            assert_eq!(Ok(-1), machine.peek(5000));
+            let chunks = machine.chunks().map(|(address, words)| (address, words.len())).collect::<Vec<_>>();
+            let expected = match backend {
+                Backend::Flat => vec![(0, 1024), (4096, 5001 - 4096)],
+                _ => vec![(0, 1024), (4096, 1024)]
+            };
+            assert_eq!(expected, chunks);
+        }
+    }
+
+    #[test]
+    fn test_poke_running_program() {
+        // Outputs the word at address 1 forever, with the cached engine decoding it once
+        let mut machine = Machine::with_io(&parse_csv("104,5,1105,1,0").unwrap(), VecDeque::new(), Vec::new());
+        machine.set_engine(Engine::Cached);
+        machine.set_history(History::new(10));
+        assert_eq!(Err(ExecuteError::ExecutionLimitReached), machine.execute(4).map_err(|f| f.error));
+        machine.poke(1, 9).unwrap();
+        assert!(!machine.step_back());
+        assert_eq!(Err(ExecuteError::ExecutionLimitReached), machine.execute(2).map_err(|f| f.error));
+        assert_eq!(&vec![5, 5, 9], machine.output());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{Word, Sender, Receiver};
+use std::collections::VecDeque;
+use std::sync::mpsc::TryRecvError;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ReadError {
+    // Nothing available yet, the machine can be resumed once there is
+    Empty,
+    // Nothing will ever be available
+    Closed
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct WriteError;
+
+pub trait WordSource {
+    fn read(&mut self) -> Result<Word, ReadError>;
+
+    // Waits for a word if the source can, otherwise the same as read
+    fn read_blocking(&mut self) -> Result<Word, ReadError> {
+        self.read()
+    }
+
+    // Words waiting to be read, for sources that can report them without consuming anything.
+    // Channels and closures cannot, so they report nothing.
+    fn queued(&self) -> Vec<Word> {
+        Vec::new()
+    }
+
+    // Takes the words already waiting, for sources like channels that can only report them by
+    // consuming them. Closures and iterators might never run dry, so they give nothing.
+    fn take_queued(&mut self) -> Vec<Word> {
+        Vec::new()
+    }
+}
+
+pub trait WordSink {
+    fn write(&mut self, value: Word) -> Result<(), WriteError>;
+}
+
+// Reads from a closure, which returns None when no input is available yet
+pub struct FnSource<F>(pub F);
+
+// Reads from an iterator, treating its end as the input being closed
+pub struct IterSource<I>(pub I);
+
+pub struct FnSink<F>(pub F);
+
+impl WordSource for Receiver<Word> {
+    fn read(&mut self) -> Result<Word, ReadError> {
+        self.try_recv().map_err(|e| match e {
+            TryRecvError::Empty => ReadError::Empty,
+            TryRecvError::Disconnected => ReadError::Closed,
+        })
+    }
+
+    fn read_blocking(&mut self) -> Result<Word, ReadError> {
+        self.recv().map_err(|_| ReadError::Closed)
+    }
+
+    fn take_queued(&mut self) -> Vec<Word> {
+        self.try_iter().collect()
+    }
+}
+
+impl WordSource for VecDeque<Word> {
+    fn read(&mut self) -> Result<Word, ReadError> {
+        self.pop_front().ok_or(ReadError::Empty)
+    }
+
+    fn queued(&self) -> Vec<Word> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<F: FnMut() -> Option<Word>> WordSource for FnSource<F> {
+    fn read(&mut self) -> Result<Word, ReadError> {
+        (self.0)().ok_or(ReadError::Empty)
+    }
+}
+
+impl<I: Iterator<Item=Word>> WordSource for IterSource<I> {
+    fn read(&mut self) -> Result<Word, ReadError> {
+        self.0.next().ok_or(ReadError::Closed)
+    }
+}
+
+impl<T: WordSource + ?Sized> WordSource for &mut T {
+    fn read(&mut self) -> Result<Word, ReadError> {
+        (**self).read()
+    }
+
+    fn read_blocking(&mut self) -> Result<Word, ReadError> {
+        (**self).read_blocking()
+    }
+
+    fn queued(&self) -> Vec<Word> {
+        (**self).queued()
+    }
+
+    fn take_queued(&mut self) -> Vec<Word> {
+        (**self).take_queued()
+    }
+}
+
+impl<T: WordSource + ?Sized> WordSource for Box<T> {
+    fn read(&mut self) -> Result<Word, ReadError> {
+        (**self).read()
+    }
+
+    fn read_blocking(&mut self) -> Result<Word, ReadError> {
+        (**self).read_blocking()
+    }
+
+    fn queued(&self) -> Vec<Word> {
+        (**self).queued()
+    }
+
+    fn take_queued(&mut self) -> Vec<Word> {
+        (**self).take_queued()
+    }
+}
+
+impl WordSink for Sender<Word> {
+    fn write(&mut self, value: Word) -> Result<(), WriteError> {
+        self.send(value).map_err(|_| WriteError)
+    }
+}
+
+impl WordSink for VecDeque<Word> {
+    fn write(&mut self, value: Word) -> Result<(), WriteError> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+impl WordSink for Vec<Word> {
+    fn write(&mut self, value: Word) -> Result<(), WriteError> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+impl<F: FnMut(Word)> WordSink for FnSink<F> {
+    fn write(&mut self, value: Word) -> Result<(), WriteError> {
+        (self.0)(value);
+        Ok(())
+    }
+}
+
+impl<T: WordSink + ?Sized> WordSink for &mut T {
+    fn write(&mut self, value: Word) -> Result<(), WriteError> {
+        (**self).write(value)
+    }
+}
+
+impl<T: WordSink + ?Sized> WordSink for Box<T> {
+    fn write(&mut self, value: Word) -> Result<(), WriteError> {
+        (**self).write(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Machine, ExecuteError, parse_csv};
+
+    #[test]
+    fn test_buffers() {
+        let memory = parse_csv("3,9,8,9,10,9,4,9,99,-1,8").unwrap();
+        let mut machine = Machine::with_io(&memory, VecDeque::from(vec![8]), Vec::new());
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(&vec![1], machine.output());
+
+        let mut machine = Machine::with_io(&memory, VecDeque::new(), Vec::new());
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(100).map_err(|f| f.error));
+        machine.input_mut().push_back(7);
+        assert_eq!(Ok(()), machine.execute(100));
+        let (_, output) = machine.into_io();
+        assert_eq!(vec![0], output);
+    }
+
+    #[test]
+    fn test_closures_and_iterators() {
+        let memory = parse_csv("3,0,4,0,3,0,4,0,99").unwrap();
+        let mut seen = Vec::new();
+        {
+            let mut machine = Machine::with_io(&memory, IterSource(vec![5, 6].into_iter()), FnSink(|w| seen.push(w)));
+            assert_eq!(Ok(()), machine.execute(100));
+        }
+        assert_eq!(vec![5, 6], seen);
+
+        // An exhausted iterator can never provide more input
+        let mut machine = Machine::with_io(&memory, IterSource(Some(5).into_iter()), Vec::new());
+        assert_eq!(Err(ExecuteError::InputError), machine.execute(100).map_err(|f| f.error));
+
+        let mut next = 0;
+        let mut out = VecDeque::new();
+        let mut machine = Machine::with_io(&memory, FnSource(|| { next += 1; Some(next) }), &mut out);
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(vec![1, 2], out.into_iter().collect::<Vec<_>>());
+    }
+}
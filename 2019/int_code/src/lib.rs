@@ -6,83 +6,180 @@ use std::borrow::{BorrowMut};
 use std::num::{ParseIntError};
 use std::fmt;
 use std::error::Error as StdError;
-use std::sync::mpsc::TryRecvError;
 use std::convert::TryInto;
-use std::collections::HashMap;
+
+mod arith;
+mod ascii;
+mod asm;
+mod backend;
+mod cache;
+mod cfg;
+mod custom;
+mod debug;
+mod disasm;
+mod dump;
+#[cfg(any(test, feature = "fuzzing"))]
+mod fuzz;
+mod history;
+mod inspect;
+mod io;
+mod network;
+mod packet;
+mod patch;
+mod profile;
+mod save;
+mod snapshot;
+mod symbolic;
+mod thread;
+mod trace;
+pub use crate::arith::{Arithmetic, UnsupportedArithmetic};
+use crate::arith::BigCells;
+pub use crate::ascii::{AsciiInput, AsciiOutput, Text};
+pub use crate::asm::{assemble, AsmError};
+pub use crate::backend::{Backend, BackendTooSmall};
+use crate::backend::Store;
+pub use crate::cache::Engine;
+use crate::cache::DecodeCache;
+pub use crate::cfg::{control_flow, Cfg, Block, Exit};
+pub use crate::custom::{Slot, Call, OpcodeError};
+use crate::custom::{Extensions, CustomOp};
+pub use crate::debug::{Debugger, Stop, Access};
+pub use crate::disasm::{disassemble, listing, Line, Instruction, Mnemonic, Mode, Operand};
+pub use crate::dump::{MemoryDump, Radix, Difference, CsvTooLarge};
+#[cfg(feature = "fuzzing")]
+pub use crate::fuzz::{FuzzCase, Outcome, XorShift, reference};
+pub use crate::history::{History, Change};
+pub use crate::io::{WordSource, WordSink, ReadError, WriteError, FnSource, FnSink, IterSource};
+pub use crate::network::{Network, Report};
+pub use crate::packet::{PacketNetwork, Packet, PacketEvent};
+pub use crate::patch::{run_patched, search_patches};
+pub use crate::profile::{Profile, HotLoop};
+pub use crate::save::{SaveFile, LoadError};
+pub use crate::snapshot::Snapshot;
+pub use crate::symbolic::{Symbolic, Expr, Target, Path, PathEnd};
+pub use crate::trace::{Tracer, TraceEntry, IoEvent};
 
 pub type Word = i64;
-pub struct Machine {
+pub struct Machine<I = Receiver<Word>, O = Sender<Word>> {
     memory: Memory,
     pc: Word,
-    input: Receiver<Word>,
-    output: Sender<Word>,
+    input: I,
+    output: O,
     relative_base: Word,
-    waiting_input: bool
+    waiting_input: bool,
+    blocking: bool,
+    steps: u64,
+    tracer: Option<Tracer>,
+    profile: Option<Profile>,
+    history: Option<History>,
+    // Input given back by stepping backwards, read again before anything new (last first)
+    replay: Vec<Word>,
+    // Input read by the custom instruction being performed, for its history and trace
+    handler_input: Option<Word>,
+    cache: Option<DecodeCache>,
+    arithmetic: Arithmetic,
+    big: BigCells,
+    extensions: Extensions
 }
 
 const CHUNK_SIZE: usize = 1024;
 
-struct Memory {
-    memory: HashMap<usize, Box<[Word; CHUNK_SIZE]>>
+#[derive(Clone)]
+enum Memory {
+    Sparse(backend::Sparse),
+    Flat(backend::Flat),
+    Hybrid(backend::Hybrid)
 }
 struct AccessViolation(Option<Word>, Word);
 
 impl Memory {
 
     fn new(init: &[Word]) -> Memory {
-        let mut m = Memory{ memory: HashMap::with_capacity((init.len() + CHUNK_SIZE - 1) / CHUNK_SIZE) };
-        let mut i = 0;
-        for chunk in init.chunks(CHUNK_SIZE) {
-            let mut tmp = Box::new([0; CHUNK_SIZE]);
-            tmp[..chunk.len()].copy_from_slice(chunk);
-            if m.memory.insert(i, tmp).is_some() {
-                panic!("Overwrote memory on initialisation");
+        Memory::with_backend(init, Backend::Sparse)
+    }
+
+    fn with_backend(init: &[Word], backend: Backend) -> Memory {
+        match backend {
+            Backend::Sparse => Memory::Sparse(backend::Sparse::new(init)),
+            Backend::Flat => Memory::Flat(backend::Flat::new(init)),
+            Backend::Hybrid => Memory::Hybrid(backend::Hybrid::new(init)),
+        }
+    }
+
+    fn backend(&self) -> Backend {
+        match self {
+            Memory::Sparse(_) => Backend::Sparse,
+            Memory::Flat(_) => Backend::Flat,
+            Memory::Hybrid(_) => Backend::Hybrid,
+        }
+    }
+
+    fn convert(&self, backend: Backend) -> Result<Memory, BackendTooSmall> {
+        let mut converted = Memory::with_backend(&[], backend);
+        for (address, words) in self.segments() {
+            for (i, &w) in words.iter().enumerate().filter(|&(_, &w)| w != 0) {
+                let address = (address + i) as Word;
+                if converted.write(OutputParameter(None, address), w).is_err() {
+                    return Err(BackendTooSmall{ backend, address });
+                }
             }
-            i += 1;
         }
-        m
+        Ok(converted)
+    }
+
+    // Chunk aligned regions of memory that may be non-zero, in address order
+    fn segments(&self) -> Vec<(usize, &[Word])> {
+        match self {
+            Memory::Sparse(m) => m.segments(),
+            Memory::Flat(m) => m.segments(),
+            Memory::Hybrid(m) => m.segments(),
+        }
     }
 
     fn read(&self, address: Parameter) -> Result<Word, AccessViolation> {
         match address {
             Immediate(val) => Ok(val),
-            Position(relative, addr) => {
-                let (chunk_id, sub_chunk_index) = Memory::address(relative, addr)?;
-                match self.memory.get(&chunk_id) {
-                    None => Ok(0),
-                    Some(chunk) => Ok(chunk[sub_chunk_index]),
-                }
-            }
+            Position(relative, addr) => Ok(self.get(Memory::address(relative, addr)?))
         }
     }
     fn read_position(&self, addr: Word) -> Result<Word, AccessViolation> {
-        let (chunk_id, sub_chunk_index) = Memory::address(None, addr)?;
-        match self.memory.get(&chunk_id) {
-            None => Ok(0),
-            Some(chunk) => Ok(chunk[sub_chunk_index]),
-        }
+        Ok(self.get(Memory::address(None, addr)?))
+    }
+    // The nth word of the instruction at pc, which is out of range when pc + n overflows
+    fn operand(&self, pc: Word, n: Word) -> Result<Word, AccessViolation> {
+        Ok(self.get(Memory::address(Some(pc), n)?))
     }
     fn write(&mut self, OutputParameter(rel_base, addr): OutputParameter, new_val: Word) -> Result<(), AccessViolation> {
-        let (chunk_id, sub_chunk_index) = Memory::address(rel_base, addr)?;
-
-        self.get_chunk_mut(chunk_id)[sub_chunk_index] = new_val;
-        Ok(())
+        let address = Memory::address(rel_base, addr)?;
+        let stored = match self {
+            Memory::Sparse(m) => m.set(address, new_val),
+            Memory::Flat(m) => m.set(address, new_val),
+            Memory::Hybrid(m) => m.set(address, new_val),
+        };
+        if stored {
+            Ok(())
+        }
+        else {
+            Err(AccessViolation(rel_base, rel_base.unwrap_or(0) + addr))
+        }
     }
 
-    fn get_chunk_mut(&mut self, chunk_id: usize) -> &mut Box<[Word; CHUNK_SIZE]> {
-        self.memory
-            .entry(chunk_id)
-            .or_insert_with(|| Box::new([0; CHUNK_SIZE]))
+    fn get(&self, address: usize) -> Word {
+        match self {
+            Memory::Sparse(m) => m.get(address),
+            Memory::Flat(m) => m.get(address),
+            Memory::Hybrid(m) => m.get(address),
+        }
     }
 
-    fn address(relative_base: Option<Word>, addr: Word) -> Result<(usize, usize), AccessViolation> {
+    fn address(relative_base: Option<Word>, addr: Word) -> Result<usize, AccessViolation> {
         if let Some(addr) = relative_base.unwrap_or(0).checked_add(addr) {
             if addr < 0 {
                 return Err(AccessViolation(relative_base, addr));
             }
             let rslt: Result<usize, _> = addr.try_into();
             match rslt {
-                Ok(addr_u) => Ok((addr_u / CHUNK_SIZE, addr_u % CHUNK_SIZE)),
+                Ok(addr_u) => Ok(addr_u),
                 Err(_) => Err(AccessViolation(relative_base, addr))
             }
         }
@@ -92,7 +189,7 @@ impl Memory {
     }
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub enum ExecuteError {
     InputRequired,
     InputError,
@@ -104,6 +201,17 @@ pub enum ExecuteError {
     MemoryAccessViolation(Option<Word>, Word)
 }
 
+// An ExecuteError together with the state of the machine when it happened
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Fault {
+    pub error: ExecuteError,
+    pub pc: Word,
+    // None if the pc itself could not be read
+    pub opcode: Option<Word>,
+    pub relative_base: Word,
+    pub steps: u64
+}
+
 pub fn parse_csv(csv: &str) -> Result<Vec<Word>, ParseIntError> {
     csv.split(',')
         .map(|x| x.parse::<Word>())
@@ -146,28 +254,78 @@ impl StdError for ExecuteError {
     }
 }
 
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Intcode machine fault: {}", self.error)?;
+        writeln!(f, "    pc:            {}", self.pc)?;
+        match self.opcode {
+            Some(op) => writeln!(f, "    opcode:        {}", op)?,
+            None => writeln!(f, "    opcode:        <unreadable>")?,
+        }
+        writeln!(f, "    relative base: {}", self.relative_base)?;
+        write!(f, "    executed:      {} instructions", self.steps)
+    }
+}
+impl StdError for Fault {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
 impl Machine {
     pub fn with_channels(memory: &[Word], input: Receiver<Word>, output: Sender<Word>) -> Machine {
-        Machine{ memory: Memory::new(memory), pc: 0, input, output, relative_base: 0, waiting_input: false }
+        Machine::with_io(memory, input, output)
     }
 
     pub fn new(memory: &[Word]) -> (Machine, Sender<Word>, Receiver<Word>) {
         let (input_write, input): (Sender<Word>, Receiver<Word>) = channel();
         let (output, output_read): (Sender<Word>, Receiver<Word>) = channel();
 
-        (Machine{ memory: Memory::new(memory), pc: 0, input, output, relative_base: 0, waiting_input: false },
-        input_write, output_read)
+        (Machine::with_io(memory, input, output), input_write, output_read)
+    }
+}
+
+impl<I, O> Machine<I, O> {
+    pub fn with_io(memory: &[Word], input: I, output: O) -> Machine<I, O> {
+        Machine{ memory: Memory::new(memory), pc: 0, input, output, relative_base: 0, waiting_input: false, blocking: false, steps: 0, tracer: None, profile: None, history: None, replay: Vec::new(), handler_input: None, cache: None, arithmetic: Arithmetic::Checked, big: BigCells::default(), extensions: Extensions::default() }
+    }
+
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        &mut self.output
+    }
+
+    pub fn into_io(self) -> (I, O) {
+        (self.input, self.output)
+    }
+
+    // Number of instructions executed so far, not counting the final halt
+    pub fn steps(&self) -> u64 {
+        self.steps
     }
+}
 
-    pub fn execute(&mut self, limit: u32) -> Result<(), ExecuteError> {
+impl<I: WordSource, O: WordSink> Machine<I, O> {
+
+    pub fn execute(&mut self, limit: u32) -> Result<(), Fault> {
         let mut lim = limit;
         loop {
             if lim == 0 {
-                return Err(ExecuteError::ExecutionLimitReached);
+                return Err(self.fault(ExecuteError::ExecutionLimitReached));
             }
 
-            let op = Operation::decode(self)?;
-            match self.step(op)? {
+            match self.step()? {
                 StepResult::Executed => (),
                 StepResult::Halt => return Ok(())
             }
@@ -175,56 +333,101 @@ impl Machine {
         }
     }
 
-    fn step(&mut self, op: Operation) -> Result<StepResult, StepError> {
+    pub fn step(&mut self) -> Result<StepResult, Fault> {
+        let decoded = match self.cache.as_mut() {
+            Some(cache) => cache.decode(&self.memory, self.pc, self.relative_base),
+            None => Operation::decode(&self.memory, self.pc, self.relative_base)
+        };
+        let op = decoded
+            .or_else(|e| self.extensions.decode(&self.memory, self.pc, self.relative_base, e))
+            .map_err(|e| self.fault(e.into()))?;
+        let pc = self.pc;
+        let change = self.history.as_ref().map(|_| self.begin_change(&op));
+        let result = if self.tracer.is_some() {
+            self.perform_traced(op)
+        }
+        else {
+            self.perform(op)
+        };
+        if let (Some(cache), Some(out), true) = (self.cache.as_mut(), op.output(), result.is_ok()) {
+            if let Some(address) = out.address() {
+                cache.invalidate(address);
+            }
+        }
+        if let (Some(profile), true) = (self.profile.as_mut(), result.is_ok()) {
+            profile.record(pc, &op, &self.memory, self.pc);
+        }
+        if let (Some(change), Ok(StepResult::Executed)) = (change, &result) {
+            self.finish_change(change, &op);
+        }
+        match result {
+            Ok(StepResult::Executed) => {
+                self.steps += 1;
+                Ok(StepResult::Executed)
+            },
+            Ok(StepResult::Halt) => Ok(StepResult::Halt),
+            Err(e) => Err(self.fault(e.into()))
+        }
+    }
+
+    pub(crate) fn fault(&self, error: ExecuteError) -> Fault {
+        Fault{
+            error,
+            pc: self.pc,
+            opcode: self.memory.read_position(self.pc).ok(),
+            relative_base: self.relative_base,
+            steps: self.steps
+        }
+    }
+
+    fn perform(&mut self, op: Operation) -> Result<StepResult, StepError> {
+        #[cfg(feature = "bigint")]
+        {
+            if self.arithmetic == Arithmetic::Unbounded {
+                if let Some(result) = self.perform_unbounded(op)? {
+                    return Ok(result);
+                }
+            }
+        }
         let memory = self.memory.borrow_mut();
         match op {
             Add(a, b, out) => {
                 let a_val = memory.read(a)?;
                 let b_val = memory.read(b)?;
-                memory.write(out, a_val.checked_add(b_val).ok_or(StepError::ArithmeticOverflow)?)?;
-                self.pc += 4;
+                memory.write(out, self.arithmetic.add(a_val, b_val).ok_or(StepError::ArithmeticOverflow)?)?;
+                self.pc = advance(self.pc, 4)?;
             },
             Multiply(a, b, out) => {
                 let a_val = memory.read(a)?;
                 let b_val = memory.read(b)?;
-                memory.write(out, a_val.checked_mul(b_val).ok_or(StepError::ArithmeticOverflow)?)?;
-                self.pc += 4;
+                memory.write(out, self.arithmetic.mul(a_val, b_val).ok_or(StepError::ArithmeticOverflow)?)?;
+                self.pc = advance(self.pc, 4)?;
             },
             Input(out) => {
-                let readval = self.input.try_recv();
-                match readval {
-                    Ok(rslt) => memory.write(out, rslt)?,
-                    Err(a) => {
-                        if self.waiting_input {
-                            return Err(StepError::NoProgress)
-                        }
-                        else if let TryRecvError::Empty = a {
-                            self.waiting_input = true;
-                            return Err(StepError::InputRequired)
-                        }
-                        else {
-                            return Err(StepError::InputError)
-                        }
-                    }
+                let rslt = next_input(&mut self.replay, &mut self.input, self.blocking, &mut self.waiting_input)?;
+                memory.write(out, rslt)?;
+                if let Some(address) = out.address() {
+                    self.big.forget(address);
                 }
-                self.pc += 2;
+                self.pc = advance(self.pc, 2)?;
             },
             Output(a) => {
-                match self.output.send(memory.read(a)?) {
+                #[allow(clippy::single_match)]
+                match self.output.write(memory.read(a)?) {
                     Err(_) => {
                         self.waiting_input = false;
                         return Err(StepError::OutputError)
                     },
                     _ => ()
                 }
-                self.pc += 2;
+                self.pc = advance(self.pc, 2)?;
             },
             JumpIfTrue(a, new_pc) => {
                 if memory.read(a)? != 0 {
                     self.pc = memory.read(new_pc)?;
                 }
                 else {
-                    self.pc += 3;
+                    self.pc = advance(self.pc, 3)?;
                 }
             },
             JumpIfFalse(a, new_pc) => {
@@ -232,7 +435,7 @@ impl Machine {
                     self.pc = memory.read(new_pc)?;
                 }
                 else {
-                    self.pc += 3;
+                    self.pc = advance(self.pc, 3)?;
                 }
             },
             LessThan(a, b, out) => {
@@ -242,7 +445,7 @@ impl Machine {
                 else {
                     memory.write(out, 0)?;
                 }
-                self.pc += 4;
+                self.pc = advance(self.pc, 4)?;
             },
             Equals(a, b, out) => {
                 if memory.read(a)? == memory.read(b)? {
@@ -251,54 +454,103 @@ impl Machine {
                 else {
                     memory.write(out, 0)?;
                 }
-                self.pc += 4;
+                self.pc = advance(self.pc, 4)?;
             },
             AddRelativeBase(a) => {
                 let a_val = memory.read(a)?;
                 self.relative_base = self.relative_base.checked_add(a_val).ok_or(StepError::ArithmeticOverflow)?;
-                self.pc += 2;
+                self.pc = advance(self.pc, 2)?;
             },
             Halt => {
                 self.waiting_input = false;
                 return Ok(StepResult::Halt)
-            }
+            },
+            Custom(op) => return self.perform_custom(op)
         }
         self.waiting_input = false;
         Ok(StepResult::Executed)
     }
 }
 
+// The next word for IN or a custom instruction, taking any input given back by stepping backwards
+// first. Finding no input twice in a row without anything executing in between is NoProgress.
+pub(crate) fn next_input(replay: &mut Vec<Word>, input: &mut dyn WordSource, blocking: bool, waiting_input: &mut bool) -> Result<Word, StepError> {
+    let readval = if let Some(w) = replay.pop() {
+        Ok(w)
+    }
+    else if blocking {
+        input.read_blocking()
+    }
+    else {
+        input.read()
+    };
+    match readval {
+        Ok(rslt) => Ok(rslt),
+        Err(a) => {
+            if *waiting_input {
+                Err(StepError::NoProgress)
+            }
+            else if let ReadError::Empty = a {
+                *waiting_input = true;
+                Err(StepError::InputRequired)
+            }
+            else {
+                Err(StepError::InputError)
+            }
+        }
+    }
+}
+
+// The pc of the instruction after one of `size` words at `pc`, which is out of range when the
+// instruction ends at the last address
+pub(crate) fn advance(pc: Word, size: Word) -> Result<Word, StepError> {
+    pc.checked_add(size).ok_or(StepError::MemoryAccessViolation(Some(pc), size))
+}
+
 impl From<AccessViolation> for StepError {
     fn from(AccessViolation(rb, a): AccessViolation) -> Self {
         StepError::MemoryAccessViolation(rb, a)
     }
 }
 
+impl From<AccessViolation> for ExecuteError {
+    fn from(AccessViolation(rb, a): AccessViolation) -> Self {
+        ExecuteError::MemoryAccessViolation(rb, a)
+    }
+}
+
 impl From<AccessViolation> for DecodeError {
     fn from(AccessViolation(rb, a): AccessViolation) -> Self {
         DecodeError::AccessViolation(rb, a)
     }
 }
 
-enum StepResult {
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum StepResult {
     Executed,
     Halt
 }
+#[derive(Clone)]
 enum StepError {
     InputRequired,
     InputError,
     OutputError,
     NoProgress,
     ArithmeticOverflow,
-    MemoryAccessViolation(Option<Word>, Word)
+    MemoryAccessViolation(Option<Word>, Word),
+    // Returned by a custom instruction's handler
+    Handler(ExecuteError)
 }
 
+#[derive(Copy, Clone)]
 struct OutputParameter(Option<Word>, Word);
+#[derive(Copy, Clone)]
 enum Parameter {
     Immediate(Word),
     Position(Option<Word>, Word)
 }
 
+#[derive(Copy, Clone)]
 enum Operation {
     Add(Parameter, Parameter, OutputParameter),
     Multiply(Parameter, Parameter, OutputParameter),
@@ -309,7 +561,8 @@ enum Operation {
     LessThan(Parameter, Parameter, OutputParameter),
     Equals(Parameter, Parameter, OutputParameter),
     AddRelativeBase(Parameter),
-    Halt
+    Halt,
+    Custom(CustomOp)
 }
 
 enum DecodeError {
@@ -333,24 +586,53 @@ impl From<StepError> for ExecuteError {
             StepError::InputError => ExecuteError::InputError,
             StepError::InputRequired => ExecuteError::InputRequired,
             StepError::OutputError => ExecuteError::OutputError,
-            StepError::NoProgress => ExecuteError::NoProgress
+            StepError::NoProgress => ExecuteError::NoProgress,
+            StepError::Handler(e) => e
         }
     }
 }
 
 impl Operation {
-    fn decode(machine: &Machine) -> Result<Operation, DecodeError> {
-        let memory = &machine.memory;
-        let relative_base = machine.relative_base;
-        let pc = machine.pc;
+    fn inputs(&self) -> [Option<Parameter>; 2] {
+        match *self {
+            Add(a, b, _) | Multiply(a, b, _) | LessThan(a, b, _) | Equals(a, b, _) => [Some(a), Some(b)],
+            JumpIfTrue(a, b) | JumpIfFalse(a, b) => [Some(a), Some(b)],
+            Output(a) | AddRelativeBase(a) => [Some(a), None],
+            Input(_) | Halt => [None, None],
+            Custom(op) => op.inputs(),
+        }
+    }
+
+    // The inputs actually read when the operation runs on `memory`, where a jump only reads its
+    // target when the condition takes it
+    pub(crate) fn reads(&self, memory: &Memory) -> [Option<Parameter>; 2] {
+        match *self {
+            JumpIfTrue(a, b) | JumpIfFalse(a, b) => {
+                let on_true = matches!(self, JumpIfTrue(..));
+                let taken = memory.read(a).is_ok_and(|c| (c != 0) == on_true);
+                [Some(a), if taken { Some(b) } else { None }]
+            },
+            _ => self.inputs()
+        }
+    }
+
+    fn output(&self) -> Option<OutputParameter> {
+        match *self {
+            Add(_, _, out) | Multiply(_, _, out) | LessThan(_, _, out) | Equals(_, _, out) | Input(out) => Some(out),
+            Custom(op) => op.output(),
+            _ => None
+        }
+    }
+
+    fn decode(memory: &Memory, pc: Word, relative_base: Word) -> Result<Operation, DecodeError> {
         let full_opcode = memory.read_position(pc)?;
         let opcode = full_opcode % 100;
         let params = full_opcode / 100;
         match opcode {
             1 | 2 | 7 | 8 => {
-                let pos1 = memory.read_position(pc + 1)?;
-                let pos2 = memory.read_position(pc + 2)?;
-                let pos3 = memory.read_position(pc + 3)?;
+                let pos1 = memory.operand(pc, 1)?;
+                let pos2 = memory.operand(pc, 2)?;
+                let pos3 = memory.operand(pc, 3)?;
                 let p1 = match params % 10 {
                     0 => Position (None, pos1),
                     1 => Immediate(pos1),
@@ -373,29 +655,32 @@ impl Operation {
                     2 => Ok(Multiply(p1, p2, pout)),
                     7 => Ok(LessThan(p1, p2, pout)),
                     8 => Ok(Equals(p1, p2, pout)),
+                    #[allow(clippy::needless_return)]
                     _ => return Err(DecodeError::InvalidOpcode(full_opcode))
                 }
             },
             3 => {
-                let pos1 = memory.read_position(pc + 1)?;
+                let pos1 = memory.operand(pc, 1)?;
                 match params {
                     0 => Ok(Input(OutputParameter(None, pos1))),
                     2 => Ok(Input(OutputParameter(Some(relative_base), pos1))),
+                    #[allow(clippy::needless_return)]
                     _ => return Err(DecodeError::InvalidOpcode(full_opcode))
                 }
             },
             4 => {
-                let pos1 = memory.read_position(pc + 1)?;
+                let pos1 = memory.operand(pc, 1)?;
                 match params {
                     0 => Ok(Output(Position (None, pos1))),
                     1 => Ok(Output(Immediate(pos1))),
                     2 => Ok(Output(Position (Some(relative_base), pos1))),
+                    #[allow(clippy::needless_return)]
                     _ => return Err(DecodeError::InvalidOpcode(full_opcode))
                 }
             },
             5 | 6 => {
-                let pos1 = memory.read_position(pc + 1)?;
-                let pos2 = memory.read_position(pc + 2)?;
+                let pos1 = memory.operand(pc, 1)?;
+                let pos2 = memory.operand(pc, 2)?;
                 let p1 = match params % 10 {
                     0 => Position (None, pos1),
                     1 => Immediate(pos1),
@@ -411,15 +696,17 @@ impl Operation {
                 match opcode {
                     5 => Ok(JumpIfTrue(p1, p2)),
                     6 => Ok(JumpIfFalse(p1, p2)),
+                    #[allow(clippy::needless_return)]
                     _ => return Err(DecodeError::InvalidOpcode(full_opcode))
                 }
             },
             9 => {
-                let pos1 = memory.read_position(pc + 1)?;
+                let pos1 = memory.operand(pc, 1)?;
                 match params {
                     0 => Ok(AddRelativeBase(Position (None, pos1))),
                     1 => Ok(AddRelativeBase(Immediate(pos1))),
                     2 => Ok(AddRelativeBase(Position (Some(relative_base), pos1))),
+                    #[allow(clippy::needless_return)]
                     _ => return Err(DecodeError::InvalidOpcode(full_opcode))
                 }
             },
@@ -441,27 +728,31 @@ mod tests {
     use super::*;
     use crate::ExecuteError::*;
 
+    fn new(memory: &[Word], backend: Backend) -> (Machine, Sender<Word>, Receiver<Word>) {
+        let (input_write, input) = channel();
+        let (output, output_read) = channel();
+        (Machine::with_backend(memory, backend, input, output), input_write, output_read)
+    }
+
     #[test]
     fn test_program() {
         let input_mem = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
         let memory = parse_csv(input_mem).unwrap();
 
-        {
-            let (mut machine_7, input_write, out_read) = Machine::new(&memory);
+        for &backend in Backend::ALL.iter() {
+            let (mut machine_7, input_write, out_read) = new(&memory, backend);
             input_write.send(7).unwrap();
             assert_eq!(Ok(()), machine_7.execute(1000));
             let vals = out_read.try_iter().collect::<Vec<_>>();
             assert_eq!(vec![999], vals);
-        }
-        {
-            let (mut machine_8, input_write, out_read) = Machine::new(&memory);
+
+            let (mut machine_8, input_write, out_read) = new(&memory, backend);
             input_write.send(8).unwrap();
             assert_eq!(Ok(()), machine_8.execute(1000));
             let vals = out_read.try_iter().collect::<Vec<_>>();
             assert_eq!(vec![1000], vals);
-        }
-        {
-            let (mut machine_9, input_write, out_read) = Machine::new(&memory);
+
+            let (mut machine_9, input_write, out_read) = new(&memory, backend);
             input_write.send(9).unwrap();
             assert_eq!(Ok(()), machine_9.execute(1000));
             let vals = out_read.try_iter().collect::<Vec<_>>();
@@ -474,8 +765,10 @@ mod tests {
         let input_mem = "1106,0,0";
         let memory = parse_csv(input_mem).unwrap();
 
-        let (mut machine, _, _) = Machine::new(&memory);
-        assert_eq!(Err(ExecutionLimitReached), machine.execute(10));
+        for &backend in Backend::ALL.iter() {
+            let (mut machine, _, _) = new(&memory, backend);
+            assert_eq!(Err(ExecutionLimitReached), machine.execute(10).map_err(|f| f.error));
+        }
     }
 
     #[test]
@@ -483,16 +776,18 @@ mod tests {
         let input_mem = "3,2,0";
         let memory = parse_csv(input_mem).unwrap();
 
-        let (mut machine, _inp, _) = Machine::new(&memory);
-        assert_eq!(Err(InputRequired), machine.execute(10));
-        // If input is still required on a second call, but it is still not available,
-        // we expect a NoProgress error as the machine was unable to do any work
-        assert_eq!(Err(NoProgress), machine.execute(10));
-
-        let (mut machine, _, _) = Machine::new(&memory);
-        // use of _ parameter for input-source causes it to be released, and the corresponding receiver to be closed
-        // Complete failure when trying to get input
-        assert_eq!(Err(InputError), machine.execute(10));
+        for &backend in Backend::ALL.iter() {
+            let (mut machine, _inp, _) = new(&memory, backend);
+            assert_eq!(Err(InputRequired), machine.execute(10).map_err(|f| f.error));
+            // If input is still required on a second call, but it is still not available,
+            // we expect a NoProgress error as the machine was unable to do any work
+            assert_eq!(Err(NoProgress), machine.execute(10).map_err(|f| f.error));
+
+            let (mut machine, _, _) = new(&memory, backend);
+            // use of _ parameter for input-source causes it to be released, and the corresponding receiver to be closed
+            // Complete failure when trying to get input
+            assert_eq!(Err(InputError), machine.execute(10).map_err(|f| f.error));
+        }
     }
 
     #[test]
@@ -500,14 +795,16 @@ mod tests {
         let input_mem = "104,1,99";
         let memory = parse_csv(input_mem).unwrap();
 
-        let (mut machine, _, out) = Machine::new(&memory);
-        assert_eq!(Ok(()), machine.execute(10));
-        assert_eq!(vec![1], out.try_iter().collect::<Vec<Word>>());
+        for &backend in Backend::ALL.iter() {
+            let (mut machine, _, out) = new(&memory, backend);
+            assert_eq!(Ok(()), machine.execute(10));
+            assert_eq!(vec![1], out.try_iter().collect::<Vec<Word>>());
 
-        let (mut machine, _, _) = Machine::new(&memory);
-        // use of _ parameter for output-target causes it to be released, and the corresponding sender to be closed
-        // Complete failure when trying to write output
-        assert_eq!(Err(OutputError), machine.execute(10));
+            let (mut machine, _, _) = new(&memory, backend);
+            // use of _ parameter for output-target causes it to be released, and the corresponding sender to be closed
+            // Complete failure when trying to write output
+            assert_eq!(Err(OutputError), machine.execute(10).map_err(|f| f.error));
+        }
     }
 
     #[test]
@@ -515,8 +812,10 @@ mod tests {
         let input_mem = "1,0,0,0";
         let memory = parse_csv(input_mem).unwrap();
 
-        let (mut machine, _, _) = Machine::new(&memory);
-        assert_eq!(Err(ExecuteError::UnrecognisedOpcode(0)), machine.execute(10));
+        for &backend in Backend::ALL.iter() {
+            let (mut machine, _, _) = new(&memory, backend);
+            assert_eq!(Err(ExecuteError::UnrecognisedOpcode(0)), machine.execute(10).map_err(|f| f.error));
+        }
     }
 
     #[test]
@@ -524,36 +823,63 @@ mod tests {
         let input_mem = "1,0,0,-1";
         let memory = parse_csv(input_mem).unwrap();
 
-        let (mut machine, _, _) = Machine::new(&memory);
-        assert_eq!(Err(ExecuteError::MemoryAccessViolation(None, -1)), machine.execute(10));
+        for &backend in Backend::ALL.iter() {
+            let (mut machine, _, _) = new(&memory, backend);
+            assert_eq!(Err(ExecuteError::MemoryAccessViolation(None, -1)), machine.execute(10).map_err(|f| f.error));
+        }
+    }
+
+    #[test]
+    fn test_fault_context() {
+        let mem = vec![109,-3,21101,1,1,0,99];
+        for &backend in Backend::ALL.iter() {
+            let (mut machine, _, _) = new(&mem, backend);
+            let fault = machine.execute(10).unwrap_err();
+            assert_eq!(Fault{ error: MemoryAccessViolation(Some(-3), -3), pc: 2, opcode: Some(21101), relative_base: -3, steps: 1 }, fault);
+            assert_eq!(concat!(
+                "Intcode machine fault: MemoryAccessViolation(-3, -3)\n",
+                "    pc:            2\n",
+                "    opcode:        21101\n",
+                "    relative base: -3\n",
+                "    executed:      1 instructions"), fault.to_string());
+
+            let (mut machine, _, _) = new(&[1105,1,-1], backend);
+            assert_eq!(None, machine.execute(10).unwrap_err().opcode);
+        }
     }
 
     #[test]
     fn test_quine() {
         let mem = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
-        let (mut machine, _, out) = Machine::new(&mem);
-        assert_eq!(Ok(()), machine.execute(100));
-        let result = out.try_iter().collect::<Vec<Word>>();
-        assert_eq!(mem, result);
+        for &backend in Backend::ALL.iter() {
+            let (mut machine, _, out) = new(&mem, backend);
+            assert_eq!(Ok(()), machine.execute(100));
+            let result = out.try_iter().collect::<Vec<Word>>();
+            assert_eq!(mem, result);
+        }
     }
 
     #[test]
     fn test_bignum() {
         let mem = vec![1102,34915192,34915192,7,4,7,99,0];
-        let (mut machine, _, out) = Machine::new(&mem);
-        assert_eq!(Ok(()), machine.execute(100));
-        let result = out.try_iter().collect::<Vec<Word>>();
-        assert_eq!(1, result.len());
-        assert_eq!(16, result[0].to_string().len());
+        for &backend in Backend::ALL.iter() {
+            let (mut machine, _, out) = new(&mem, backend);
+            assert_eq!(Ok(()), machine.execute(100));
+            let result = out.try_iter().collect::<Vec<Word>>();
+            assert_eq!(1, result.len());
+            assert_eq!(16, result[0].to_string().len());
+        }
     }
 
     #[test]
     fn test_mid() {
         let mem = vec![104,1125899906842624,99];
-        let (mut machine, _, out) = Machine::new(&mem);
-        assert_eq!(Ok(()), machine.execute(100));
-        let result = out.try_iter().collect::<Vec<Word>>();
-        assert_eq!(1, result.len());
-        assert_eq!(1125899906842624, result[0]);
+        for &backend in Backend::ALL.iter() {
+            let (mut machine, _, out) = new(&mem, backend);
+            assert_eq!(Ok(()), machine.execute(100));
+            let result = out.try_iter().collect::<Vec<Word>>();
+            assert_eq!(1, result.len());
+            assert_eq!(1125899906842624, result[0]);
+        }
     }
 }
@@ -0,0 +1,265 @@
+use crate::{Machine, Fault, ExecuteError, WordSink, WriteError, Word, Sender, Receiver, channel};
+use std::collections::VecDeque;
+use std::thread;
+
+// A set of machines with each one's output wired to the inputs of others. It only describes the
+// machines, every run starts them afresh.
+#[derive(Clone, Default)]
+pub struct Network {
+    programs: Vec<Vec<Word>>,
+    seeds: Vec<Vec<Word>>,
+    links: Vec<(usize, usize)>
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    // Ok for machines that halted, otherwise the fault that stopped them. Machines left waiting
+    // for input that never arrived report InputRequired or NoProgress.
+    pub results: Vec<Result<(), Fault>>,
+    // Everything each machine output, whether or not anything was connected to receive it
+    pub outputs: Vec<Vec<Word>>
+}
+
+impl Network {
+    pub fn new() -> Network {
+        Network::default()
+    }
+
+    // One machine per phase setting, each seeded with its phase and feeding the next
+    pub fn chain(memory: &[Word], phases: &[Word]) -> Network {
+        let mut network = Network::new();
+        for &phase in phases {
+            let node = network.add(memory);
+            network.seed(node, &[phase]);
+            if node > 0 {
+                network.connect(node - 1, node);
+            }
+        }
+        network
+    }
+
+    // A chain with the last machine also feeding back into the first
+    pub fn ring(memory: &[Word], phases: &[Word]) -> Network {
+        let mut network = Network::chain(memory, phases);
+        if !phases.is_empty() {
+            network.connect(phases.len() - 1, 0);
+        }
+        network
+    }
+
+    // Adds a machine, returning its index
+    pub fn add(&mut self, memory: &[Word]) -> usize {
+        self.programs.push(memory.to_vec());
+        self.seeds.push(Vec::new());
+        self.programs.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    // Sends every output of `from` to `to` as well as anywhere else it already goes
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(from < self.len() && to < self.len(), "no machine {} in a network of {}", from.max(to), self.len());
+        self.links.push((from, to));
+    }
+
+    // Queues input for a machine ahead of anything sent to it by other machines
+    pub fn seed(&mut self, node: usize, words: &[Word]) {
+        self.seeds[node].extend_from_slice(words);
+    }
+
+    fn targets(&self, node: usize) -> impl Iterator<Item=usize> + '_ {
+        self.links.iter().filter(move |&&(from, _)| from == node).map(|&(_, to)| to)
+    }
+
+    // Runs every machine on one thread, taking turns until each is blocked, and stopping once
+    // none can make progress. Each machine executes at most `limit` instructions.
+    pub fn run(&self, limit: u32) -> Report {
+        let mut machines = self.programs.iter()
+            .zip(self.seeds.iter())
+            .map(|(program, seed)| Machine::with_io(program, VecDeque::from(seed.clone()), Vec::new()))
+            .collect::<Vec<_>>();
+        let mut results = vec![None; machines.len()];
+        let mut outputs = vec![Vec::new(); machines.len()];
+
+        loop {
+            let mut progress = false;
+            for node in 0..machines.len() {
+                if !waiting(&results[node]) {
+                    continue;
+                }
+                let machine = &mut machines[node];
+                let steps = machine.steps();
+                let result = machine.execute(remaining(limit, steps));
+                progress |= machine.steps() != steps;
+                let sent = machine.output_mut().drain(..).collect::<Vec<_>>();
+
+                for to in self.targets(node) {
+                    machines[to].input_mut().extend(sent.iter().cloned());
+                }
+                outputs[node].extend(sent);
+                results[node] = Some(result);
+            }
+            if !progress || !results.iter().any(waiting) {
+                break;
+            }
+        }
+        Report{ results: results.into_iter().map(|r| r.unwrap_or(Ok(()))).collect(), outputs }
+    }
+
+    // Runs each machine on its own thread, blocking while waiting for input. Unlike `run` this
+    // never returns if the machines deadlock waiting on each other.
+    pub fn run_threaded(&self) -> Report {
+        let (senders, receivers): (Vec<Sender<Word>>, Vec<Receiver<Word>>) = (0..self.len()).map(|_| channel()).unzip();
+        for (sender, seed) in senders.iter().zip(self.seeds.iter()) {
+            for &w in seed {
+                sender.send(w).unwrap();
+            }
+        }
+
+        let handles = receivers.into_iter()
+            .enumerate()
+            .map(|(node, input)| {
+                let output = Fanout{ targets: self.targets(node).map(|to| senders[to].clone()).collect(), log: Vec::new() };
+                let mut machine = Machine::with_io(&self.programs[node], input, output);
+                thread::spawn(move || {
+                    let result = machine.run_blocking();
+                    (result, machine.into_io().1.log)
+                })
+            })
+            .collect::<Vec<_>>();
+        // Machines with nothing feeding them see their input close once their seeds run out
+        drop(senders);
+
+        let (results, outputs) = handles.into_iter()
+            .enumerate()
+            .map(|(node, handle)| handle.join().unwrap_or_else(|panic| {
+                let message = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                panic!("machine {} of the network panicked: {}", node, message)
+            }))
+            .unzip();
+        Report{ results, outputs }
+    }
+}
+
+// What is left of a machine's instruction limit after it has run some steps
+fn remaining(limit: u32, steps: u64) -> u32 {
+    (limit as u64).saturating_sub(steps) as u32
+}
+
+// Machines that haven't run yet or are blocked on input
+fn waiting(result: &Option<Result<(), Fault>>) -> bool {
+    matches!(result, None |
+        Some(Err(Fault{ error: ExecuteError::InputRequired, .. })) |
+        Some(Err(Fault{ error: ExecuteError::NoProgress, .. })))
+}
+
+impl Report {
+    pub fn halted(&self) -> Vec<usize> {
+        (0..self.results.len()).filter(|&node| self.results[node].is_ok()).collect()
+    }
+
+    pub fn faults(&self) -> Vec<(usize, &Fault)> {
+        self.results.iter()
+            .enumerate()
+            .filter_map(|(node, r)| r.as_ref().err().map(|f| (node, f)))
+            .collect()
+    }
+
+    pub fn last_output(&self, node: usize) -> Option<Word> {
+        self.outputs[node].last().cloned()
+    }
+}
+
+// Copies every word to each connected machine. Machines that have already stopped are skipped,
+// as in a ring the last machine's final output goes to a first machine that has halted.
+struct Fanout {
+    targets: Vec<Sender<Word>>,
+    log: Vec<Word>
+}
+
+impl WordSink for Fanout {
+    fn write(&mut self, value: Word) -> Result<(), WriteError> {
+        for target in self.targets.iter() {
+            let _ = target.send(value);
+        }
+        self.log.push(value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_csv;
+
+    #[test]
+    fn test_chain() {
+        // Reads a phase and a value, and outputs their sum
+        let memory = parse_csv("3,11,3,12,1,11,12,13,4,13,99,0,0,0").unwrap();
+        let mut network = Network::chain(&memory, &[1, 2, 3]);
+        network.seed(0, &[10]);
+        for report in [network.run(100), network.run_threaded()] {
+            assert_eq!(vec![vec![11], vec![13], vec![16]], report.outputs);
+            assert_eq!(vec![0, 1, 2], report.halted());
+        }
+    }
+
+    #[test]
+    fn test_ring() {
+        // Day 7's second feedback loop example
+        let memory = parse_csv("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5").unwrap();
+        let mut network = Network::ring(&memory, &[9, 8, 7, 6, 5]);
+        network.seed(0, &[0]);
+        for report in [network.run(1000), network.run_threaded()] {
+            assert_eq!(Some(139629729), report.last_output(4));
+            assert_eq!(5, report.halted().len());
+        }
+    }
+
+    #[test]
+    fn test_graph() {
+        let sum = parse_csv("3,11,3,12,1,11,12,13,4,13,99,0,0,0").unwrap();
+        let echo = parse_csv("3,5,4,5,99,0").unwrap();
+        let mut network = Network::new();
+        let source = network.add(&echo);
+        let left = network.add(&sum);
+        let right = network.add(&sum);
+        network.connect(source, left);
+        network.connect(source, right);
+        network.seed(source, &[4]);
+        network.seed(left, &[1]);
+        let report = network.run(100);
+        assert_eq!(vec![vec![4], vec![5], vec![]], report.outputs);
+        assert_eq!(vec![0, 1], report.halted());
+        // Right never gets a second input
+        let faults = report.faults();
+        assert_eq!(1, faults.len());
+        assert_eq!((right, ExecuteError::NoProgress), (faults[0].0, faults[0].1.error));
+
+        // Threaded, right sees its input close instead
+        let report = network.run_threaded();
+        assert_eq!(ExecuteError::InputError, report.results[right].as_ref().unwrap_err().error);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut network = Network::new();
+        network.add(&[1105, 1, 0]);
+        network.add(&[104, 1, 99]);
+        let report = network.run(50);
+        assert_eq!(ExecuteError::ExecutionLimitReached, report.results[0].as_ref().unwrap_err().error);
+        assert_eq!(50, report.results[0].as_ref().unwrap_err().steps);
+        assert_eq!(vec![1], report.halted());
+
+        // A machine that has run past u32::MAX steps has nothing left
+        assert_eq!((20, 0, 0), (remaining(50, 30), remaining(50, 80), remaining(50, (1 << 32) + 10)));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{Machine, Fault, ExecuteError, Word};
+use std::collections::VecDeque;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Packet {
+    pub from: Word,
+    pub to: Word,
+    pub x: Word,
+    pub y: Word
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum PacketEvent {
+    // A machine sent a packet. Packets to addresses with no machine or NAT are dropped.
+    Sent(Packet),
+    // The network went idle and the NAT sent its last packet to machine 0
+    Woke(Packet)
+}
+
+// Machines addressed 0 to N-1 exchanging (destination, x, y) packets. Each machine reads its
+// address first, then -1 whenever its queue is empty. Packets sent to the NAT address are held,
+// and when a whole round passes with every queue empty and nothing sent, the last one is passed
+// on to machine 0.
+pub struct PacketNetwork {
+    machines: Vec<Machine<VecDeque<Word>, Vec<Word>>>,
+    halted: Vec<bool>,
+    nat_address: Word,
+    nat: Option<Packet>,
+    rounds: u64
+}
+
+impl PacketNetwork {
+    pub fn new(memory: &[Word], size: usize, nat_address: Word) -> PacketNetwork {
+        let machines = (0..size)
+            .map(|address| Machine::with_io(memory, VecDeque::from(vec![address as Word]), Vec::new()))
+            .collect();
+        PacketNetwork{ machines, halted: vec![false; size], nat_address, nat: None, rounds: 0 }
+    }
+
+    // The packet the NAT would send if the network went idle now
+    pub fn nat(&self) -> Option<Packet> {
+        self.nat
+    }
+
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+
+    pub fn machine(&self, address: usize) -> &Machine<VecDeque<Word>, Vec<Word>> {
+        &self.machines[address]
+    }
+
+    // Runs each machine in turn until it blocks on input, with at most `limit` instructions each
+    pub fn round(&mut self, limit: u32) -> Result<Vec<PacketEvent>, (usize, Fault)> {
+        let mut events = Vec::new();
+        let mut idle = true;
+        for address in 0..self.machines.len() {
+            if self.halted[address] {
+                continue;
+            }
+            let machine = &mut self.machines[address];
+            if machine.input().is_empty() {
+                machine.input_mut().push_back(-1);
+            }
+            else {
+                idle = false;
+            }
+            match machine.execute(limit) {
+                Ok(()) => self.halted[address] = true,
+                Err(Fault{ error: ExecuteError::InputRequired, .. }) => (),
+                Err(fault) => return Err((address, fault))
+            }
+
+            // A partly written packet stays in the buffer until the rest of it is sent
+            let complete = machine.output().len() / 3 * 3;
+            let sent = machine.output_mut().drain(..complete).collect::<Vec<_>>();
+            for p in sent.chunks(3) {
+                let packet = Packet{ from: address as Word, to: p[0], x: p[1], y: p[2] };
+                self.deliver(packet);
+                events.push(PacketEvent::Sent(packet));
+                idle = false;
+            }
+        }
+        self.rounds += 1;
+
+        if idle {
+            if let Some(packet) = self.nat {
+                self.machines[0].input_mut().extend(&[packet.x, packet.y]);
+                events.push(PacketEvent::Woke(packet));
+            }
+        }
+        Ok(events)
+    }
+
+    // Runs rounds until `stop` returns true for an event, which is returned. Gives up with None
+    // after `rounds` rounds.
+    pub fn run_until<F: FnMut(&PacketEvent) -> bool>(&mut self, rounds: u64, limit: u32, mut stop: F) -> Result<Option<PacketEvent>, (usize, Fault)> {
+        for _ in 0..rounds {
+            if let Some(event) = self.round(limit)?.into_iter().find(|e| stop(e)) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        if packet.to == self.nat_address {
+            self.nat = Some(packet);
+        }
+        else if packet.to >= 0 && (packet.to as usize) < self.machines.len() {
+            self.machines[packet.to as usize].input_mut().extend(&[packet.x, packet.y]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    // Machine 0 starts a packet that each machine passes to the next with y incremented, and the
+    // last sends to the NAT
+    fn relay(last: Word) -> Vec<Word> {
+        assemble(&format!("
+                IN   addr
+                JT   addr, #loop
+                OUT  #1
+                OUT  #0
+                OUT  #100
+        loop:   IN   x
+                EQ   x, #-1, tmp
+                JT   tmp, #loop
+                IN   y
+                ADD  addr, #1, dest
+                ADD  y, #1, y
+                EQ   addr, #{}, tmp
+                JF   tmp, #send
+                ADD  #255, #0, dest
+        send:   OUT  dest
+                OUT  x
+                OUT  y
+                JT   #1, #loop
+        addr:   .data 0
+        x:      .data 0
+        y:      .data 0
+        dest:   .data 0
+        tmp:    .data 0", last)).unwrap()
+    }
+
+    #[test]
+    fn test_relay() {
+        let mut network = PacketNetwork::new(&relay(2), 3, 255);
+        let events = network.round(1000).unwrap();
+        assert_eq!(vec![
+            PacketEvent::Sent(Packet{ from: 0, to: 1, x: 0, y: 100 }),
+            PacketEvent::Sent(Packet{ from: 1, to: 2, x: 0, y: 101 }),
+            PacketEvent::Sent(Packet{ from: 2, to: 255, x: 0, y: 102 })], events);
+        assert_eq!(Some(Packet{ from: 2, to: 255, x: 0, y: 102 }), network.nat());
+
+        // The next round is idle, so the NAT wakes machine 0
+        assert_eq!(vec![PacketEvent::Woke(Packet{ from: 2, to: 255, x: 0, y: 102 })], network.round(1000).unwrap());
+        let woke = network.run_until(10, 1000, |e| match e {
+            PacketEvent::Woke(p) => p.y > 110,
+            _ => false
+        });
+        assert_eq!(Ok(Some(PacketEvent::Woke(Packet{ from: 2, to: 255, x: 0, y: 111 }))), woke);
+        assert_eq!(8, network.rounds());
+    }
+
+    #[test]
+    fn test_idle_and_fault() {
+        // A round that sends packets isn't idle, so the NAT holds on to the last one until the next
+        let woke = |e: &PacketEvent| matches!(e, PacketEvent::Woke(_));
+        let mut network = PacketNetwork::new(&relay(2), 3, 255);
+        let packet = Packet{ from: 2, to: 255, x: 0, y: 102 };
+        assert_eq!(Ok(None), network.run_until(1, 1000, woke));
+        assert_eq!(Some(packet), network.nat());
+        assert_eq!(Ok(Some(PacketEvent::Woke(packet))), network.run_until(1, 1000, woke));
+        assert_eq!((2, &VecDeque::from(vec![0, 102])), (network.rounds(), network.machine(0).input()));
+        // Without a NAT the packets stop at machine 2 and the network just idles
+        let mut network = PacketNetwork::new(&relay(5), 3, 255);
+        assert_eq!(Ok(None), network.run_until(5, 1000, |e| matches!(e, PacketEvent::Woke(_))));
+        assert_eq!(None, network.nat());
+
+        let mut network = PacketNetwork::new(&[3, 100, 1105, 1, 2], 2, 255);
+        let (address, fault) = network.round(50).unwrap_err();
+        assert_eq!((0, ExecuteError::ExecutionLimitReached), (address, fault.error));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{Machine, ExecuteError, OutputParameter, Word, History};
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+impl<I, O> Machine<I, O> {
+    // Overwrites cells of memory, usually before a run. Patching can't be stepped back over, so it
+    // also clears any undo history.
+    pub fn patch(&mut self, cells: &[(Word, Word)]) -> Result<(), ExecuteError> {
+        for &(address, value) in cells {
+            self.memory.write(OutputParameter(None, address), value)?;
+            self.big.forget(address);
+            if let Some(cache) = self.cache.as_mut() {
+                cache.invalidate(address);
+            }
+        }
+        if let Some(history) = self.history.as_mut() {
+            *history = History::new(history.capacity());
+        }
+        Ok(())
+    }
+}
+
+// Runs a program with some cells patched and no input, returning the value left at address 0
+pub fn run_patched(program: &[Word], cells: &[(Word, Word)], limit: u32) -> Result<Word, ExecuteError> {
+    let mut machine = Machine::with_io(program, VecDeque::new(), Vec::new());
+    machine.patch(cells)?;
+    machine.execute(limit).map_err(|f| f.error)?;
+    Ok(machine.memory.read_position(0)?)
+}
+
+// Searches every combination of values for the given addresses, in parallel, for one that leaves
+// the target at address 0. Runs that fail count as misses. Returns the values of the first match
+// in order, with the last address varying fastest, so the result doesn't depend on the scheduling.
+pub fn search_patches(program: &[Word], addresses: &[Word], values: RangeInclusive<Word>, target: Word, limit: u32) -> Option<Vec<Word>> {
+    let base = (*values.end() as i128 - *values.start() as i128 + 1).max(0) as u128;
+    let total = base.checked_pow(addresses.len() as u32).filter(|&t| t < u64::MAX as u128)? as u64;
+    let candidate = |index: u64| {
+        let mut rest = index as u128;
+        let mut patch = vec![0; addresses.len()];
+        for value in patch.iter_mut().rev() {
+            *value = (*values.start() as i128 + (rest % base) as i128) as Word;
+            rest /= base;
+        }
+        patch
+    };
+
+    let next = AtomicU64::new(0);
+    let found = AtomicU64::new(u64::MAX);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= total || index > found.load(Ordering::Relaxed) {
+                    break;
+                }
+                let cells = addresses.iter().cloned().zip(candidate(index)).collect::<Vec<_>>();
+                if run_patched(program, &cells, limit) == Ok(target) {
+                    found.fetch_min(index, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+    match found.into_inner() {
+        u64::MAX => None,
+        index => Some(candidate(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_csv, Engine};
+
+    // Leaves the noun at address 1 times 100 plus the verb at address 6 at address 0
+    const NOUN_VERB: &str = "1102,0,100,0,1001,0,0,0,99";
+
+    #[test]
+    fn test_patch() {
+        let program = parse_csv(NOUN_VERB).unwrap();
+        assert_eq!(Ok(1234), run_patched(&program, &[(1, 12), (6, 34)], 10));
+        assert_eq!(Err(ExecuteError::MemoryAccessViolation(None, -1)), run_patched(&program, &[(-1, 0)], 10));
+        assert_eq!(Err(ExecuteError::ExecutionLimitReached), run_patched(&program, &[(1, 12), (6, 34)], 2));
+
+        // The cached engine sees a patched instruction it has already decoded
+        let mut machine = Machine::with_io(&parse_csv("104,5,3,20,1105,1,0").unwrap(), VecDeque::from(vec![0]), Vec::new());
+        machine.set_engine(Engine::Cached);
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(10).map_err(|f| f.error));
+        machine.patch(&[(1, 9)]).unwrap();
+        machine.input_mut().push_back(0);
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(10).map_err(|f| f.error));
+        assert_eq!((&vec![5, 5, 9], 9), (machine.output(), machine.snapshot().memory()[1]));
+    }
+
+    #[test]
+    fn test_search() {
+        let program = parse_csv(NOUN_VERB).unwrap();
+        assert_eq!(Some(vec![31, 46]), search_patches(&program, &[1, 6], 0..=99, 3146, 10));
+        assert_eq!(None, search_patches(&program, &[1, 6], 0..=99, 10000, 10));
+        assert_eq!(None, search_patches(&program, &[1, 6], RangeInclusive::new(5, 4), 0, 10));
+        // The first match in order wins, here noun -100 and verb 0 rather than noun -99 and verb -100
+        assert_eq!(Some(vec![-100, 0]), search_patches(&program, &[1, 6], -100..=100, -10000, 10));
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{Machine, Memory, Operation, Mnemonic, Word, control_flow};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// Execution counts gathered while a machine runs. Only instructions that complete are counted.
+#[derive(Clone, Default, Debug)]
+pub struct Profile {
+    executed: HashMap<Word, u64>,
+    opcodes: HashMap<Mnemonic, u64>,
+    reads: HashMap<Word, u64>,
+    writes: HashMap<Word, u64>,
+    // Taken jumps to the same or an earlier address, keyed by (jump address, target)
+    back_edges: HashMap<(Word, Word), u64>
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct HotLoop {
+    // Target of the backward jump
+    pub start: Word,
+    // Address of the jump instruction closing the loop
+    pub end: Word,
+    pub iterations: u64,
+    // Instructions executed at addresses from start to end, inclusive
+    pub instructions: u64
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub(crate) fn record(&mut self, pc: Word, op: &Operation, memory: &Memory, next_pc: Word) {
+        *self.executed.entry(pc).or_insert(0) += 1;
+        let mnemonic = op.mnemonic();
+        *self.opcodes.entry(mnemonic).or_insert(0) += 1;
+        let (reads, write) = op.accesses(memory);
+        for &address in reads.iter().flatten() {
+            *self.reads.entry(address).or_insert(0) += 1;
+        }
+        if let Some(address) = write {
+            *self.writes.entry(address).or_insert(0) += 1;
+        }
+        if matches!(mnemonic, Mnemonic::Jt | Mnemonic::Jf) && next_pc <= pc {
+            *self.back_edges.entry((pc, next_pc)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.executed.values().sum()
+    }
+
+    pub fn executed(&self, pc: Word) -> u64 {
+        self.executed.get(&pc).cloned().unwrap_or(0)
+    }
+
+    pub fn opcode(&self, mnemonic: Mnemonic) -> u64 {
+        self.opcodes.get(&mnemonic).cloned().unwrap_or(0)
+    }
+
+    pub fn reads(&self, address: Word) -> u64 {
+        self.reads.get(&address).cloned().unwrap_or(0)
+    }
+
+    pub fn writes(&self, address: Word) -> u64 {
+        self.writes.get(&address).cloned().unwrap_or(0)
+    }
+
+    // The `count` most executed addresses, busiest first
+    pub fn hottest(&self, count: usize) -> Vec<(Word, u64)> {
+        top(&self.executed, count)
+    }
+
+    // Addresses read or written, busiest first, with their (reads, writes)
+    pub fn heatmap(&self, count: usize) -> Vec<(Word, u64, u64)> {
+        let mut touched = self.reads.keys().chain(self.writes.keys()).cloned().collect::<Vec<_>>();
+        touched.sort();
+        touched.dedup();
+        let mut heat = touched.into_iter()
+            .map(|a| (a, self.reads(a), self.writes(a)))
+            .collect::<Vec<_>>();
+        heat.sort_by_key(|&(a, r, w)| (std::cmp::Reverse(r + w), a));
+        heat.truncate(count);
+        heat
+    }
+
+    // Loops found from taken backward jumps, most instructions first
+    pub fn hot_loops(&self, count: usize) -> Vec<HotLoop> {
+        let mut loops = self.back_edges.iter()
+            .map(|(&(end, start), &iterations)| HotLoop{
+                start,
+                end,
+                iterations,
+                instructions: self.executed.iter()
+                    .filter(|&(&pc, _)| start <= pc && pc <= end)
+                    .map(|(_, &n)| n)
+                    .sum()
+            })
+            .collect::<Vec<_>>();
+        loops.sort_by_key(|l| (std::cmp::Reverse(l.instructions), l.start, l.end));
+        loops.truncate(count);
+        loops
+    }
+
+    // Ranges of statically reachable code in `program` that never ran, as [start, end)
+    pub fn unexecuted(&self, program: &[Word]) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        let cfg = control_flow(program);
+        for instruction in cfg.blocks().flat_map(|b| b.instructions.iter()) {
+            if self.executed(instruction.address as Word) > 0 {
+                continue;
+            }
+            let end = instruction.address + instruction.size();
+            match ranges.last_mut() {
+                Some(last) if last.1 == instruction.address => last.1 = end,
+                _ => ranges.push((instruction.address, end)),
+            }
+        }
+        ranges
+    }
+
+    // Human readable summary, listing the top `count` entries of each table
+    pub fn summary(&self, program: &[Word], count: usize) -> String {
+        let total = self.total();
+        let percent = |n: u64| if total == 0 { 0.0 } else { n as f64 * 100.0 / total as f64 };
+        let mut out = String::new();
+        writeln!(out, "executed {} instructions at {} addresses", total, self.executed.len()).unwrap();
+
+        writeln!(out, "opcodes:").unwrap();
+        let mut opcodes = self.opcodes.iter().map(|(&m, &n)| (m, n)).collect::<Vec<_>>();
+        opcodes.sort_by_key(|&(m, n)| (std::cmp::Reverse(n), m.to_string()));
+        for (mnemonic, n) in opcodes {
+            writeln!(out, "    {:<4} {:>12} {:>6.1}%", mnemonic.to_string(), n, percent(n)).unwrap();
+        }
+
+        writeln!(out, "hot loops:").unwrap();
+        for l in self.hot_loops(count) {
+            writeln!(out, "    {:>6}..={:<6} {:>10} iterations {:>12} instructions {:>6.1}%", l.start, l.end, l.iterations, l.instructions, percent(l.instructions)).unwrap();
+        }
+
+        writeln!(out, "hottest addresses:").unwrap();
+        for (pc, n) in self.hottest(count) {
+            writeln!(out, "    {:>6} {:>12} {:>6.1}%", pc, n, percent(n)).unwrap();
+        }
+
+        writeln!(out, "busiest memory:").unwrap();
+        for (address, reads, writes) in self.heatmap(count) {
+            writeln!(out, "    {:>6} {:>10} reads {:>10} writes", address, reads, writes).unwrap();
+        }
+
+        writeln!(out, "never executed:").unwrap();
+        for (start, end) in self.unexecuted(program) {
+            writeln!(out, "    {:>6}..{}", start, end).unwrap();
+        }
+        out
+    }
+}
+
+fn top(counts: &HashMap<Word, u64>, count: usize) -> Vec<(Word, u64)> {
+    let mut entries = counts.iter().map(|(&a, &n)| (a, n)).collect::<Vec<_>>();
+    entries.sort_by_key(|&(a, n)| (std::cmp::Reverse(n), a));
+    entries.truncate(count);
+    entries
+}
+
+impl<I, O> Machine<I, O> {
+    pub fn set_profile(&mut self, profile: Profile) {
+        self.profile = Some(profile);
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_counts() {
+        let program = assemble("
+                IN   n
+        loop:   MUL  acc, #2, acc
+                ADD  n, #-1, n
+                JT   n, #loop
+                OUT  acc
+                JF   n, #end
+                OUT  #-1
+        end:    HLT
+        n:      .data 0
+        acc:    .data 1").unwrap();
+        let mut machine = Machine::with_io(&program, VecDeque::from(vec![10]), Vec::new());
+        machine.set_profile(Profile::new());
+        assert_eq!(Ok(()), machine.execute(1000));
+        assert_eq!(&vec![1024], machine.output());
+
+        let profile = machine.take_profile().unwrap();
+        assert_eq!(34, profile.total());
+        assert_eq!((10, 10, 1), (profile.executed(2), profile.opcode(Mnemonic::Jt), profile.opcode(Mnemonic::Jf)));
+        // n is written by IN and each ADD, and read by each ADD and JT
+        assert_eq!(vec![(21, 21, 11), (22, 11, 10)], profile.heatmap(2));
+        assert_eq!(vec![HotLoop{ start: 2, end: 10, iterations: 9, instructions: 30 }], profile.hot_loops(5));
+        assert_eq!(vec![(18, 20)], profile.unexecuted(&program));
+
+        let summary = profile.summary(&program, 1);
+        assert!(summary.starts_with("executed 34 instructions at 7 addresses\nopcodes:\n    ADD            10   29.4%\n"));
+        assert!(summary.contains("hot loops:\n         2..=10              9 iterations           30 instructions   88.2%\n"));
+        assert!(summary.ends_with("never executed:\n        18..20\n"));
+    }
+
+    #[test]
+    fn test_untaken_jump() {
+        // Only the taken JF reads its target, the JT at 3 never reads 9
+        let program = vec![6, 7, 8, 5, 7, 9, 99, 0, 3, 0];
+        let mut machine = Machine::with_io(&program, VecDeque::new(), Vec::new());
+        machine.set_profile(Profile::new());
+        assert_eq!(Ok(()), machine.execute(10));
+        let profile = machine.profile().unwrap();
+        assert_eq!((2, 1, 0), (profile.reads(7), profile.reads(8), profile.reads(9)));
+    }
+}
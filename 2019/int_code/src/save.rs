@@ -0,0 +1,594 @@
+use crate::{Machine, Memory, Snapshot, Arithmetic, UnsupportedArithmetic, BigCells, OutputParameter, WordSource, Word, Sender, Receiver, channel};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::{TryFrom, TryInto};
+use std::error::Error as StdError;
+use std::fmt;
+
+const MAGIC: &[u8; 4] = b"ICM\0";
+const VERSION: Word = 2;
+
+// Far deeper than the save format ever nests, but shallow enough to keep the parser off the end of the stack
+const MAX_DEPTH: usize = 32;
+
+// A snapshot plus the input that was queued for the machine when it was saved
+#[derive(Clone)]
+pub struct SaveFile {
+    pub snapshot: Snapshot,
+    pub input: Vec<Word>
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum LoadError {
+    // Not parseable at all, with the byte offset of the problem
+    Malformed(usize, String),
+    // Parsed, but not a machine state this version understands
+    Invalid(String)
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Malformed(offset, message) => write!(f, "Malformed save file at byte {}: {}", offset, message),
+            LoadError::Invalid(message) => write!(f, "Invalid save file: {}", message),
+        }
+    }
+}
+impl StdError for LoadError {}
+
+impl<I: WordSource, O> Machine<I, O> {
+    // Input waiting in a channel can only be seen by taking it, so it moves into the machine,
+    // which reads it before anything sent later
+    pub fn save_state(&mut self) -> SaveFile {
+        let taken = self.input.take_queued();
+        self.replay.splice(0..0, taken.into_iter().rev());
+        let input = self.replay.iter().rev().cloned().chain(self.input.queued()).collect();
+        SaveFile{ snapshot: self.snapshot(), input }
+    }
+}
+
+impl SaveFile {
+    pub fn new(snapshot: Snapshot, input: Vec<Word>) -> SaveFile {
+        SaveFile{ snapshot, input }
+    }
+
+    pub fn resume(&self) -> (Machine, Sender<Word>, Receiver<Word>) {
+        let (input_write, input) = channel();
+        let (output, output_read) = channel();
+        for &w in self.input.iter() {
+            input_write.send(w).unwrap();
+        }
+        (self.snapshot.resume_with_io(input, output), input_write, output_read)
+    }
+
+    pub fn resume_buffered(&self) -> Machine<VecDeque<Word>, Vec<Word>> {
+        self.snapshot.resume_with_io(self.input.iter().cloned().collect(), Vec::new())
+    }
+
+    // Accepts either format, telling them apart by the binary magic number
+    pub fn load(data: &[u8]) -> Result<SaveFile, LoadError> {
+        if data.starts_with(MAGIC) {
+            SaveFile::from_bytes(data)
+        }
+        else {
+            let text = std::str::from_utf8(data)
+                .map_err(|e| LoadError::Malformed(e.valid_up_to(), "not UTF-8".to_string()))?;
+            SaveFile::from_json(text)
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let s = &self.snapshot;
+        let mut out = String::new();
+        out.push_str("{\n");
+        out.push_str(&format!("  \"version\": {},\n", VERSION));
+        out.push_str(&format!("  \"pc\": {},\n", s.pc));
+        out.push_str(&format!("  \"relative_base\": {},\n", s.relative_base));
+        out.push_str(&format!("  \"waiting_input\": {},\n", s.waiting_input));
+        out.push_str(&format!("  \"steps\": {},\n", s.steps));
+        out.push_str(&format!("  \"arithmetic\": \"{}\",\n", s.arithmetic.name()));
+        let exact = s.big.to_decimal().iter()
+            .map(|(address, value)| format!("{{\"address\": {}, \"value\": \"{}\"}}", address, value))
+            .collect::<Vec<_>>();
+        out.push_str(&format!("  \"exact\": [{}],\n", exact.join(",")));
+        out.push_str(&format!("  \"input\": {},\n", json_list(&self.input)));
+        out.push_str("  \"memory\": [");
+        for (i, (address, words)) in segments(&s.memory).iter().enumerate() {
+            if i != 0 {
+                out.push(',');
+            }
+            out.push_str(&format!("\n    {{\"address\": {}, \"words\": {}}}", address, json_list(words)));
+        }
+        out.push_str("\n  ]\n}\n");
+        out
+    }
+
+    pub fn from_json(text: &str) -> Result<SaveFile, LoadError> {
+        let mut parser = Parser{ text: text.as_bytes(), pos: 0, depth: 0 };
+        let value = parser.value()?;
+        parser.whitespace();
+        if parser.pos != text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        let fields = value.object("save file")?;
+        let version = field(fields, "version")?.number("version")?;
+        if version != VERSION {
+            return Err(LoadError::Invalid(format!("unsupported version {}", version)));
+        }
+        let pc = field(fields, "pc")?.number("pc")?;
+        let relative_base = field(fields, "relative_base")?.number("relative_base")?;
+        let waiting_input = match field(fields, "waiting_input")? {
+            Json::Bool(b) => *b,
+            _ => return Err(LoadError::Invalid("waiting_input must be a boolean".to_string()))
+        };
+        let steps = count("steps", field(fields, "steps")?.number("steps")?)?;
+        let arithmetic = field(fields, "arithmetic")?.string("arithmetic")?;
+        let arithmetic = Arithmetic::from_name(arithmetic)
+            .ok_or_else(|| LoadError::Invalid(format!("unknown arithmetic {}", arithmetic)))?;
+        let exact = field(fields, "exact")?.array("exact")?.iter()
+            .map(|cell| {
+                let cell = cell.object("exact value")?;
+                Ok((field(cell, "address")?.number("address")?, field(cell, "value")?.string("value")?.to_string()))
+            })
+            .collect::<Result<Vec<_>, LoadError>>()?;
+        let big = exact_values(arithmetic, &exact)?;
+        let input = field(fields, "input")?.numbers("input")?;
+        let mut memory = Memory::new(&[]);
+        for segment in field(fields, "memory")?.array("memory")? {
+            let segment = segment.object("memory segment")?;
+            let address = field(segment, "address")?.number("address")?;
+            let words = field(segment, "words")?.numbers("words")?;
+            write_segment(&mut memory, address, &words)?;
+        }
+        Ok(SaveFile{ snapshot: Snapshot{ memory, pc, relative_base, waiting_input, steps, arithmetic, big }, input })
+    }
+
+    // Little-endian: magic, version, pc, relative base, waiting flag, step count, arithmetic mode, then the input
+    // words, memory segments and exact values, each preceded by their count. Exact values are an address and the
+    // length of the decimal text that follows.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let s = &self.snapshot;
+        let segments = segments(&s.memory);
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        for w in [VERSION, s.pc, s.relative_base, s.waiting_input as Word, s.steps as Word, arithmetic_code(s.arithmetic), self.input.len() as Word].iter() {
+            out.extend_from_slice(&w.to_le_bytes());
+        }
+        for w in self.input.iter() {
+            out.extend_from_slice(&w.to_le_bytes());
+        }
+        out.extend_from_slice(&(segments.len() as Word).to_le_bytes());
+        for (address, words) in segments.iter() {
+            out.extend_from_slice(&address.to_le_bytes());
+            out.extend_from_slice(&(words.len() as Word).to_le_bytes());
+            for w in words.iter() {
+                out.extend_from_slice(&w.to_le_bytes());
+            }
+        }
+        let exact = s.big.to_decimal();
+        out.extend_from_slice(&(exact.len() as Word).to_le_bytes());
+        for (address, value) in exact.iter() {
+            out.extend_from_slice(&address.to_le_bytes());
+            out.extend_from_slice(&(value.len() as Word).to_le_bytes());
+            out.extend_from_slice(value.as_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<SaveFile, LoadError> {
+        if !data.starts_with(MAGIC) {
+            return Err(LoadError::Malformed(0, "missing magic number".to_string()));
+        }
+        let mut reader = Reader{ data, pos: MAGIC.len() };
+        let version = reader.word()?;
+        if version != VERSION {
+            return Err(LoadError::Invalid(format!("unsupported version {}", version)));
+        }
+        let pc = reader.word()?;
+        let relative_base = reader.word()?;
+        let waiting_input = reader.word()? != 0;
+        let steps = count("steps", reader.word()?)?;
+        let code = reader.word()?;
+        let arithmetic = usize::try_from(code).ok()
+            .and_then(|i| Arithmetic::ALL.get(i).cloned())
+            .ok_or_else(|| LoadError::Invalid(format!("unknown arithmetic {}", code)))?;
+        let input_len = count("input length", reader.word()?)?;
+        let input = (0..input_len).map(|_| reader.word()).collect::<Result<Vec<_>, _>>()?;
+        let mut memory = Memory::new(&[]);
+        for _ in 0..count("segment count", reader.word()?)? {
+            let address = reader.word()?;
+            let len = count("segment length", reader.word()?)?;
+            let words = (0..len).map(|_| reader.word()).collect::<Result<Vec<_>, _>>()?;
+            write_segment(&mut memory, address, &words)?;
+        }
+        let mut exact = Vec::new();
+        for _ in 0..count("exact value count", reader.word()?)? {
+            let address = reader.word()?;
+            let len = reader.word()?;
+            count("exact value length", len)?;
+            let start = reader.pos;
+            let text = std::str::from_utf8(reader.bytes(len)?)
+                .map_err(|_| LoadError::Malformed(start, "exact value is not UTF-8".to_string()))?;
+            exact.push((address, text.to_string()));
+        }
+        let big = exact_values(arithmetic, &exact)?;
+        if reader.pos != data.len() {
+            return Err(LoadError::Malformed(reader.pos, "trailing data".to_string()));
+        }
+        Ok(SaveFile{ snapshot: Snapshot{ memory, pc, relative_base, waiting_input, steps, arithmetic, big }, input })
+    }
+}
+
+// Counts are stored as words, but can never be negative
+fn count(what: &str, n: Word) -> Result<u64, LoadError> {
+    u64::try_from(n).map_err(|_| LoadError::Invalid(format!("{} can't be negative", what)))
+}
+
+fn arithmetic_code(arithmetic: Arithmetic) -> Word {
+    Arithmetic::ALL.iter().position(|&a| a == arithmetic).unwrap() as Word
+}
+
+// Exact values only exist in Unbounded mode, which this build has to support
+fn exact_values(arithmetic: Arithmetic, exact: &[(Word, String)]) -> Result<BigCells, LoadError> {
+    if !arithmetic.supported() {
+        return Err(LoadError::Invalid(UnsupportedArithmetic(arithmetic).to_string()));
+    }
+    if arithmetic != Arithmetic::Unbounded && !exact.is_empty() {
+        return Err(LoadError::Invalid(format!("exact values need unbounded arithmetic, not {}", arithmetic.name())));
+    }
+    BigCells::from_decimal(exact).map_err(LoadError::Invalid)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: Word) -> Result<&'a [u8], LoadError> {
+        let pos = self.pos;
+        let bytes = usize::try_from(len).ok()
+            .and_then(|len| self.data.get(pos..pos.checked_add(len)?))
+            .ok_or_else(|| LoadError::Malformed(pos, "unexpected end of data".to_string()))?;
+        self.pos += bytes.len();
+        Ok(bytes)
+    }
+
+    fn word(&mut self) -> Result<Word, LoadError> {
+        Ok(Word::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
+
+// Non-empty chunks in address order, with trailing zeros trimmed
+fn segments(memory: &Memory) -> Vec<(Word, Vec<Word>)> {
+    memory.segments().into_iter()
+        .filter_map(|(address, chunk)| {
+            let len = chunk.iter().rposition(|&w| w != 0)? + 1;
+            Some((address as Word, chunk[..len].to_vec()))
+        })
+        .collect()
+}
+
+fn write_segment(memory: &mut Memory, address: Word, words: &[Word]) -> Result<(), LoadError> {
+    for (i, &w) in words.iter().enumerate() {
+        address.checked_add(i as Word)
+            .and_then(|a| memory.write(OutputParameter(None, a), w).ok())
+            .ok_or_else(|| LoadError::Invalid(format!("memory segment at {} is out of range", address)))?;
+    }
+    Ok(())
+}
+
+fn json_list(words: &[Word]) -> String {
+    let items = words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+    format!("[{}]", items.join(","))
+}
+
+// Just enough JSON for the save format: objects, arrays, integers, strings and booleans
+enum Json {
+    Object(BTreeMap<String, Json>),
+    Array(Vec<Json>),
+    Number(Word),
+    String(String),
+    Bool(bool)
+}
+
+fn field<'a>(fields: &'a BTreeMap<String, Json>, name: &str) -> Result<&'a Json, LoadError> {
+    fields.get(name).ok_or_else(|| LoadError::Invalid(format!("missing field '{}'", name)))
+}
+
+impl Json {
+    fn object(&self, what: &str) -> Result<&BTreeMap<String, Json>, LoadError> {
+        match self {
+            Json::Object(fields) => Ok(fields),
+            _ => Err(LoadError::Invalid(format!("{} must be an object", what)))
+        }
+    }
+
+    fn array(&self, what: &str) -> Result<&[Json], LoadError> {
+        match self {
+            Json::Array(items) => Ok(items),
+            _ => Err(LoadError::Invalid(format!("{} must be an array", what)))
+        }
+    }
+
+    fn number(&self, what: &str) -> Result<Word, LoadError> {
+        match self {
+            Json::Number(n) => Ok(*n),
+            _ => Err(LoadError::Invalid(format!("{} must be an integer", what)))
+        }
+    }
+
+    fn string(&self, what: &str) -> Result<&str, LoadError> {
+        match self {
+            Json::String(s) => Ok(s),
+            _ => Err(LoadError::Invalid(format!("{} must be a string", what)))
+        }
+    }
+
+    fn numbers(&self, what: &str) -> Result<Vec<Word>, LoadError> {
+        self.array(what)?.iter().map(|n| n.number(what)).collect()
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+    // Objects and arrays currently open
+    depth: usize
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> LoadError {
+        LoadError::Malformed(self.pos, message.to_string())
+    }
+
+    fn whitespace(&mut self) {
+        while self.pos < self.text.len() && self.text[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), LoadError> {
+        self.whitespace();
+        if self.text.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            Ok(())
+        }
+        else {
+            Err(self.error(&format!("expected '{}'", c as char)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, LoadError> {
+        self.whitespace();
+        match self.text.get(self.pos) {
+            Some(b'{') => {
+                self.open()?;
+                let mut fields = HashMap::new();
+                if !self.close(b'}') {
+                    loop {
+                        self.whitespace();
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        let value = self.value()?;
+                        fields.insert(key, value);
+                        if self.close(b'}') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                self.depth -= 1;
+                Ok(Json::Object(fields.into_iter().collect()))
+            },
+            Some(b'[') => {
+                self.open()?;
+                let mut items = Vec::new();
+                if !self.close(b']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.close(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                self.depth -= 1;
+                Ok(Json::Array(items))
+            },
+            Some(b'"') => self.string().map(Json::String),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'-') | Some(b'0'..=b'9') => {
+                let start = self.pos;
+                self.pos += 1;
+                while self.pos < self.text.len() && self.text[self.pos].is_ascii_digit() {
+                    self.pos += 1;
+                }
+                std::str::from_utf8(&self.text[start..self.pos]).unwrap()
+                    .parse::<Word>()
+                    .map(Json::Number)
+                    .map_err(|e| LoadError::Malformed(start, e.to_string()))
+            },
+            _ => Err(self.error("expected a value"))
+        }
+    }
+
+    // Steps into an object or array
+    fn open(&mut self) -> Result<(), LoadError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.depth += 1;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn close(&mut self, c: u8) -> bool {
+        self.whitespace();
+        if self.text.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, LoadError> {
+        if self.text[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        }
+        else {
+            Err(self.error("expected a value"))
+        }
+    }
+
+    // Escapes never appear in the save format, so they are rejected rather than decoded
+    fn string(&mut self) -> Result<String, LoadError> {
+        if self.text.get(self.pos) != Some(&b'"') {
+            return Err(self.error("expected a string"));
+        }
+        let start = self.pos + 1;
+        match self.text[start..].iter().position(|&c| c == b'"' || c == b'\\') {
+            Some(len) if self.text[start + len] == b'"' => {
+                self.pos = start + len + 1;
+                Ok(String::from_utf8_lossy(&self.text[start..start + len]).into_owned())
+            },
+            _ => Err(self.error("unsupported or unterminated string"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExecuteError, parse_csv};
+
+    // Writes its first input far away at 4100, then echoes the next two inputs
+    const PROGRAM: &str = "3,4100,3,100,4,100,3,100,4,100,99";
+
+    fn saved() -> SaveFile {
+        let memory = parse_csv(PROGRAM).unwrap();
+        let mut machine = Machine::with_io(&memory, VecDeque::from(vec![-7, 8]), Vec::new());
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(100).map_err(|f| f.error));
+        assert_eq!(&vec![8], machine.output());
+        machine.input_mut().push_back(9);
+        machine.save_state()
+    }
+
+    fn check_resumed(save: &SaveFile) {
+        let mut machine = save.resume_buffered();
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(&vec![9], machine.output());
+        assert_eq!(-7, machine.snapshot().memory.read_position(4100).ok().unwrap());
+
+        let (mut machine, _, output) = save.resume();
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(vec![9], output.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_json() {
+        let json = saved().to_json();
+        assert!(json.contains("\"input\": [9],"));
+        assert!(json.contains("{\"address\": 4096, \"words\": [0,0,0,0,-7]}"));
+        let loaded = SaveFile::from_json(&json).unwrap();
+        assert_eq!(json, loaded.to_json());
+        check_resumed(&loaded);
+    }
+
+    #[test]
+    fn test_binary() {
+        let bytes = saved().to_bytes();
+        let loaded = SaveFile::load(&bytes).unwrap();
+        assert_eq!(bytes, loaded.to_bytes());
+        check_resumed(&loaded);
+
+        assert_eq!(Err(LoadError::Malformed(bytes.len() - 8, "unexpected end of data".to_string())),
+                   SaveFile::from_bytes(&bytes[..bytes.len() - 1]).map(|_| ()));
+    }
+
+    #[test]
+    fn test_channel_input() {
+        let memory = parse_csv(PROGRAM).unwrap();
+        let (mut machine, input, output) = Machine::new(&memory);
+        input.send(-7).unwrap();
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(100).map_err(|f| f.error));
+        input.send(8).unwrap();
+        input.send(9).unwrap();
+        let save = machine.save_state();
+        assert_eq!(vec![8, 9], save.input);
+        let mut resumed = save.resume_buffered();
+        assert_eq!(Ok(()), resumed.execute(100));
+        assert_eq!(&vec![8, 9], resumed.output());
+
+        // The machine that was saved still reads the taken input, before anything new
+        input.send(10).unwrap();
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(vec![8, 9], output.try_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_bad_json() {
+        assert_eq!(Err(LoadError::Malformed(11, "expected ':'".to_string())),
+                   SaveFile::load(b"{\"version\" 1}").map(|_| ()));
+        assert_eq!(Err(LoadError::Invalid("missing field 'pc'".to_string())),
+                   SaveFile::load(b"{\"version\": 2}").map(|_| ()));
+        assert_eq!(Err(LoadError::Invalid("unsupported version 1".to_string())),
+                   SaveFile::load(b"{\"version\": 1}").map(|_| ()));
+    }
+
+    #[test]
+    fn test_negative_counts() {
+        let bytes = saved().to_bytes();
+        let with = |offset: usize, w: Word| {
+            let mut bytes = bytes.clone();
+            bytes[offset..offset + 8].copy_from_slice(&w.to_le_bytes());
+            SaveFile::from_bytes(&bytes).map(|_| ())
+        };
+        let negative = |what: &str| Err(LoadError::Invalid(format!("{} can't be negative", what)));
+        // The header words start after the magic number, and the single input word is followed by
+        // the segment count and the first segment's address and length
+        assert_eq!(negative("steps"), with(36, -1));
+        assert_eq!(negative("input length"), with(52, -1));
+        assert_eq!(negative("segment count"), with(68, -1));
+        assert_eq!(negative("segment length"), with(84, -1));
+
+        let json = saved().to_json().replace("\"steps\": 3", "\"steps\": -3");
+        assert_eq!(negative("steps"), SaveFile::from_json(&json).map(|_| ()));
+    }
+
+    #[test]
+    fn test_deep_json() {
+        let deep = "[".repeat(100000);
+        assert_eq!(Err(LoadError::Malformed(MAX_DEPTH, "nested too deeply".to_string())), SaveFile::from_json(&deep).map(|_| ()));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let (mut machine, _, _) = Machine::new(&[99]);
+        machine.set_arithmetic(Arithmetic::Wrapping).unwrap();
+        let save = machine.save_state();
+        let json = save.to_json();
+        assert!(json.contains("\"arithmetic\": \"wrapping\",\n  \"exact\": [],"));
+        for loaded in [SaveFile::from_json(&json), SaveFile::from_bytes(&save.to_bytes())] {
+            assert_eq!(Arithmetic::Wrapping, loaded.unwrap().resume_buffered().arithmetic());
+        }
+
+        // Exact values have to be too big for a word, and only go with unbounded arithmetic
+        let exact = json.replace("\"exact\": []", "\"exact\": [{\"address\": 0, \"value\": \"18446744073709551616\"}]");
+        assert_eq!(Err(LoadError::Invalid("exact values need unbounded arithmetic, not wrapping".to_string())),
+                   SaveFile::from_json(&exact).map(|_| ()));
+        let unbounded = exact.replace("wrapping", "unbounded");
+        if cfg!(feature = "bigint") {
+            assert!(SaveFile::from_json(&unbounded).is_ok());
+            assert_eq!(Err(LoadError::Invalid("exact value at 0 fits in a word".to_string())),
+                       SaveFile::from_json(&unbounded.replace("18446744073709551616", "-5")).map(|_| ()));
+        }
+        else {
+            assert_eq!(Err(LoadError::Invalid("unbounded arithmetic needs the bigint feature".to_string())),
+                       SaveFile::from_json(&unbounded).map(|_| ()));
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{Machine, Memory, History, Arithmetic, BigCells, Extensions, Word, Sender, Receiver, channel};
+
+// Everything about a machine except its I/O endpoints
+#[derive(Clone)]
+pub struct Snapshot {
+    pub(crate) memory: Memory,
+    pub(crate) pc: Word,
+    pub(crate) relative_base: Word,
+    pub(crate) waiting_input: bool,
+    pub(crate) steps: u64,
+    pub(crate) arithmetic: Arithmetic,
+    pub(crate) big: BigCells
+}
+
+impl Snapshot {
+    pub fn pc(&self) -> Word {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> Word {
+        self.relative_base
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // Memory from address 0 up to the last non-zero word
+    pub fn memory(&self) -> Vec<Word> {
+        let mut words = Vec::new();
+        for (address, segment) in self.memory.segments() {
+            if let Some(last) = segment.iter().rposition(|&w| w != 0) {
+                words.resize(address, 0);
+                words.extend_from_slice(&segment[..=last]);
+            }
+        }
+        words
+    }
+
+    pub fn resume(&self) -> (Machine, Sender<Word>, Receiver<Word>) {
+        let (input_write, input) = channel();
+        let (output, output_read) = channel();
+        (self.resume_with_io(input, output), input_write, output_read)
+    }
+
+    pub fn resume_with_io<I, O>(&self, input: I, output: O) -> Machine<I, O> {
+        Machine{
+            memory: self.memory.clone(),
+            pc: self.pc,
+            input,
+            output,
+            relative_base: self.relative_base,
+            waiting_input: self.waiting_input,
+            blocking: false,
+            steps: self.steps,
+            tracer: None,
+            profile: None,
+            history: None,
+            replay: Vec::new(),
+            handler_input: None,
+            cache: None,
+            arithmetic: self.arithmetic,
+            big: self.big.clone(),
+            extensions: Extensions::default()
+        }
+    }
+}
+
+impl<I, O> Machine<I, O> {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot{
+            memory: self.memory.clone(),
+            pc: self.pc,
+            relative_base: self.relative_base,
+            waiting_input: self.waiting_input,
+            steps: self.steps,
+            arithmetic: self.arithmetic,
+            big: self.big.clone()
+        }
+    }
+
+    // Rewinds (or fast-forwards) to a snapshot, keeping this machine's I/O endpoints and any input
+    // it has taken from them but not yet read
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory = snapshot.memory.clone();
+        self.pc = snapshot.pc;
+        self.relative_base = snapshot.relative_base;
+        self.waiting_input = snapshot.waiting_input;
+        self.steps = snapshot.steps;
+        self.arithmetic = snapshot.arithmetic;
+        self.big = snapshot.big.clone();
+        if let Some(cache) = self.cache.as_mut() {
+            cache.clear();
+        }
+        if let Some(history) = self.history.as_mut() {
+            *history = History::new(history.capacity());
+        }
+    }
+
+    pub fn fork(&self) -> (Machine, Sender<Word>, Receiver<Word>) {
+        let (input_write, input) = channel();
+        let (output, output_read) = channel();
+        (self.fork_with_io(input, output), input_write, output_read)
+    }
+
+    // The fork runs on the same engine and arithmetic, but starts without a tracer, profile or
+    // custom instructions
+    pub fn fork_with_io<I2, O2>(&self, input: I2, output: O2) -> Machine<I2, O2> {
+        let mut machine = self.snapshot().resume_with_io(input, output);
+        machine.set_engine(self.engine());
+        machine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Machine, ExecuteError, parse_csv};
+
+    // Reads two values and outputs their sum, storing it at address 13
+    const ADDER: &str = "3,11,3,12,1,11,12,13,4,13,99,0,0,0";
+
+    #[test]
+    fn test_fork() {
+        let memory = parse_csv(ADDER).unwrap();
+        let (mut machine, input, output) = Machine::new(&memory);
+        input.send(40).unwrap();
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(100).map_err(|f| f.error));
+
+        let (mut fork, fork_input, fork_output) = machine.fork();
+        assert_eq!(2, fork.snapshot().pc());
+
+        input.send(2).unwrap();
+        fork_input.send(-40).unwrap();
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(Ok(()), fork.execute(100));
+        assert_eq!(vec![42], output.try_iter().collect::<Vec<_>>());
+        assert_eq!(vec![0], fork_output.try_iter().collect::<Vec<_>>());
+        assert_eq!(parse_csv("3,11,3,12,1,11,12,13,4,13,99,40,2,42").unwrap(), machine.snapshot().memory());
+        assert_eq!(parse_csv("3,11,3,12,1,11,12,13,4,13,99,40,-40").unwrap(), fork.snapshot().memory());
+    }
+
+    #[test]
+    fn test_restore() {
+        let memory = parse_csv(ADDER).unwrap();
+        let (mut machine, input, output) = Machine::new(&memory);
+        input.send(1).unwrap();
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(100).map_err(|f| f.error));
+        let waiting = machine.snapshot();
+
+        for second in 1..=3 {
+            machine.restore(&waiting);
+            input.send(second).unwrap();
+            assert_eq!(Ok(()), machine.execute(100));
+        }
+        assert_eq!(vec![2, 3, 4], output.try_iter().collect::<Vec<_>>());
+
+        // The restored machine was already waiting, so no input at all means no progress
+        machine.restore(&waiting);
+        assert_eq!(Err(ExecuteError::NoProgress), machine.execute(100).map_err(|f| f.error));
+    }
+
+    #[test]
+    fn test_restore_after_save() {
+        let memory = parse_csv(ADDER).unwrap();
+        let (mut machine, input, output) = Machine::new(&memory);
+        let start = machine.snapshot();
+        input.send(40).unwrap();
+        input.send(2).unwrap();
+
+        // Saving takes the queued input out of the channel, restoring must not lose it
+        machine.save_state();
+        machine.restore(&start);
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(vec![42], output.try_iter().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,615 @@
+use crate::{ExecuteError, Word, advance};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+// A value computed from constants and symbols
+#[derive(Clone)]
+pub struct Expr(Rc<Node>);
+
+enum Node {
+    Const(Word),
+    Symbol(usize),
+    Add(Expr, Expr),
+    Mul(Expr, Expr),
+    Lt(Expr, Expr),
+    Eq(Expr, Expr),
+    // A read through an address that depends on symbols, from memory as it was at the time
+    Load(View, Expr)
+}
+
+// Memory as the program was loaded, overlaid with everything written since
+#[derive(Clone)]
+struct View {
+    program: Rc<Vec<Word>>,
+    cells: Rc<HashMap<Word, Expr>>
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Target {
+    // The nth word output has this value
+    Output(usize, Word),
+    // Memory at this address has this value when the machine halts
+    Memory(Word, Word)
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum PathEnd {
+    Halted,
+    // Includes InputRequired when the input runs out, and ExecutionLimitReached
+    Fault(ExecuteError),
+    // A symbolic value was used as an opcode, jump target, relative base offset or written address,
+    // at this pc
+    Symbolic(Word)
+}
+
+// One way through the program, taken when every constraint holds
+pub struct Path {
+    pub end: PathEnd,
+    pub pc: Word,
+    pub outputs: Vec<Expr>,
+    // Jump conditions, with whether they were non-zero on this path
+    pub constraints: Vec<(Expr, bool)>,
+    memory: View
+}
+
+// Runs a program with some memory cells or input words left as symbols, each with a range of
+// values it may take. Jumps on a symbolic condition explore both ways, so a loop counted by a
+// symbol is followed until the instruction limit or path limit cuts it off.
+pub struct Symbolic {
+    initial: State,
+    domains: Vec<RangeInclusive<Word>>
+}
+
+#[derive(Clone)]
+struct State {
+    memory: View,
+    pc: Word,
+    relative_base: Word,
+    input: VecDeque<Expr>,
+    outputs: Vec<Expr>,
+    constraints: Vec<(Expr, bool)>,
+    // Whether the symbolic jump at the pc is taken, when this state was forked from another that
+    // took the other side
+    decided: Option<bool>,
+    steps: u32
+}
+
+impl Expr {
+    pub fn constant(value: Word) -> Expr {
+        Expr(Rc::new(Node::Const(value)))
+    }
+
+    // The value, if it doesn't depend on any symbol
+    pub fn value(&self) -> Option<Word> {
+        match *self.0 {
+            Node::Const(value) => Some(value),
+            _ => None
+        }
+    }
+
+    // The value with each symbol given the value at its index, or None if an operation overflows or
+    // a load is out of range
+    pub fn eval(&self, values: &[Word]) -> Option<Word> {
+        match &*self.0 {
+            Node::Const(value) => Some(*value),
+            Node::Symbol(i) => values.get(*i).cloned(),
+            Node::Add(a, b) => a.eval(values)?.checked_add(b.eval(values)?),
+            Node::Mul(a, b) => a.eval(values)?.checked_mul(b.eval(values)?),
+            Node::Lt(a, b) => Some((a.eval(values)? < b.eval(values)?) as Word),
+            Node::Eq(a, b) => Some((a.eval(values)? == b.eval(values)?) as Word),
+            Node::Load(view, address) => match address.eval(values)? {
+                a if a < 0 => None,
+                a => view.get(a).eval(values)
+            },
+        }
+    }
+
+    fn symbol(index: usize) -> Expr {
+        Expr(Rc::new(Node::Symbol(index)))
+    }
+
+    // None only when both sides are constants and the result overflows
+    fn add(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a.value(), b.value()) {
+            (Some(x), Some(y)) => Expr::constant(x.checked_add(y)?),
+            (Some(0), _) => b,
+            (_, Some(0)) => a,
+            _ => Expr(Rc::new(Node::Add(a, b)))
+        })
+    }
+
+    fn mul(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a.value(), b.value()) {
+            (Some(x), Some(y)) => Expr::constant(x.checked_mul(y)?),
+            (Some(0), _) | (_, Some(0)) => Expr::constant(0),
+            (Some(1), _) => b,
+            (_, Some(1)) => a,
+            _ => Expr(Rc::new(Node::Mul(a, b)))
+        })
+    }
+
+    fn lt(a: Expr, b: Expr) -> Expr {
+        match (a.value(), b.value()) {
+            (Some(x), Some(y)) => Expr::constant((x < y) as Word),
+            _ => Expr(Rc::new(Node::Lt(a, b)))
+        }
+    }
+
+    fn eq(a: Expr, b: Expr) -> Expr {
+        match (a.value(), b.value()) {
+            (Some(x), Some(y)) => Expr::constant((x == y) as Word),
+            _ => Expr(Rc::new(Node::Eq(a, b)))
+        }
+    }
+
+    // The degree of a polynomial in the symbol, or None if it appears anywhere else
+    fn degree(&self, symbol: usize) -> Option<u32> {
+        match &*self.0 {
+            Node::Const(_) => Some(0),
+            Node::Symbol(i) => Some((*i == symbol) as u32),
+            Node::Add(a, b) => Some(a.degree(symbol)?.max(b.degree(symbol)?)),
+            Node::Mul(a, b) => Some(a.degree(symbol)? + b.degree(symbol)?),
+            Node::Lt(a, b) | Node::Eq(a, b) => match (a.degree(symbol)?, b.degree(symbol)?) {
+                (0, 0) => Some(0),
+                _ => None
+            },
+            Node::Load(..) => None,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &*self.0 {
+            Node::Const(value) => write!(f, "{}", value),
+            Node::Symbol(i) => write!(f, "s{}", i),
+            Node::Add(a, b) => write!(f, "({} + {})", a, b),
+            Node::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Node::Lt(a, b) => write!(f, "({} < {})", a, b),
+            Node::Eq(a, b) => write!(f, "({} == {})", a, b),
+            Node::Load(_, address) => write!(f, "[{}]", address),
+        }
+    }
+}
+
+impl fmt::Debug for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl View {
+    fn get(&self, address: Word) -> Expr {
+        match self.cells.get(&address) {
+            Some(expr) => expr.clone(),
+            None => Expr::constant(self.program.get(address as usize).cloned().unwrap_or(0))
+        }
+    }
+
+    fn set(&mut self, address: Word, value: Expr) {
+        Rc::make_mut(&mut self.cells).insert(address, value);
+    }
+}
+
+impl Path {
+    // The final contents of memory at an address
+    pub fn memory(&self, address: Word) -> Expr {
+        self.memory.get(address)
+    }
+
+    // Whether the symbols taking these values would lead down this path
+    pub fn feasible(&self, values: &[Word]) -> bool {
+        self.constraints.iter().all(|(condition, taken)| condition.eval(values).map(|v| v != 0) == Some(*taken))
+    }
+}
+
+impl Symbolic {
+    pub fn new(program: &[Word]) -> Symbolic {
+        let memory = View{ program: Rc::new(program.to_vec()), cells: Rc::new(HashMap::new()) };
+        let initial = State{
+            memory,
+            pc: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            decided: None,
+            steps: 0
+        };
+        Symbolic{ initial, domains: Vec::new() }
+    }
+
+    // Replaces the word at an address with a new symbol
+    pub fn symbolic_memory(&mut self, address: usize, domain: RangeInclusive<Word>) -> Expr {
+        let symbol = self.new_symbol(domain);
+        self.initial.memory.set(address as Word, symbol.clone());
+        symbol
+    }
+
+    // Queues a new symbol as the next input word
+    pub fn symbolic_input(&mut self, domain: RangeInclusive<Word>) -> Expr {
+        let symbol = self.new_symbol(domain);
+        self.initial.input.push_back(symbol.clone());
+        symbol
+    }
+
+    pub fn input(&mut self, value: Word) {
+        self.initial.input.push_back(Expr::constant(value));
+    }
+
+    pub fn symbols(&self) -> usize {
+        self.domains.len()
+    }
+
+    // Every path through the program, up to `max_paths` of them, each running for at most `limit`
+    // instructions
+    pub fn explore(&self, limit: u32, max_paths: usize) -> Vec<Path> {
+        let mut paths = Vec::new();
+        self.each_path(limit, max_paths, |path| {
+            paths.push(path);
+            false
+        });
+        paths
+    }
+
+    // Values for the symbols, in the order they were made, that make a halting run reach the
+    // target. Where there are several solutions, the one found first is returned.
+    pub fn solve(&self, target: Target, limit: u32, max_paths: usize) -> Option<Vec<Word>> {
+        let mut solution = None;
+        self.each_path(limit, max_paths, |path| {
+            solution = self.solve_path(&path, target);
+            solution.is_some()
+        });
+        solution
+    }
+
+    fn new_symbol(&mut self, domain: RangeInclusive<Word>) -> Expr {
+        self.domains.push(domain);
+        Expr::symbol(self.domains.len() - 1)
+    }
+
+    // Runs paths depth first, passing each to `visit` as it ends, until `visit` returns true
+    fn each_path<F: FnMut(Path) -> bool>(&self, limit: u32, max_paths: usize, mut visit: F) {
+        let mut pending = vec![self.initial.clone()];
+        let mut count = 0;
+        while let Some(mut state) = pending.pop() {
+            if count == max_paths {
+                return;
+            }
+            let end = state.run(limit, &mut pending);
+            count += 1;
+            let path = Path{ end, pc: state.pc, outputs: state.outputs, constraints: state.constraints, memory: state.memory };
+            if visit(path) {
+                return;
+            }
+        }
+    }
+
+    fn solve_path(&self, path: &Path, target: Target) -> Option<Vec<Word>> {
+        if path.end != PathEnd::Halted {
+            return None;
+        }
+        let (expr, value) = match target {
+            Target::Output(n, value) => (path.outputs.get(n)?.clone(), value),
+            Target::Memory(address, value) => (path.memory(address), value),
+        };
+        let mut values = self.domains.iter().map(|d| *d.start()).collect::<Vec<_>>();
+        // The last symbol can be solved for directly when the target is linear in it
+        let linear = self.domains.len().checked_sub(1).filter(|&last| matches!(expr.degree(last), Some(0..=1)));
+        if self.search(&expr, value, path, linear, 0, &mut values) {
+            Some(values)
+        }
+        else {
+            None
+        }
+    }
+
+    fn search(&self, expr: &Expr, value: Word, path: &Path, linear: Option<usize>, index: usize, values: &mut Vec<Word>) -> bool {
+        if index == self.domains.len() {
+            return expr.eval(values) == Some(value) && path.feasible(values);
+        }
+        if linear == Some(index) {
+            if let Some(x) = solve_linear(expr, value, index, values) {
+                values[index] = x;
+                return self.domains[index].contains(&x) && self.search(expr, value, path, None, index + 1, values);
+            }
+        }
+        for x in self.domains[index].clone() {
+            values[index] = x;
+            if self.search(expr, value, path, linear, index + 1, values) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+// Solves a*x + b == value for the symbol at `index`, where the expression is known to be linear in
+// it. Returns None when every x or none fits, or the coefficients overflow, leaving the caller to
+// try each value in turn.
+fn solve_linear(expr: &Expr, value: Word, index: usize, values: &mut [Word]) -> Option<Word> {
+    values[index] = 0;
+    let b = expr.eval(values)?;
+    values[index] = 1;
+    let a = expr.eval(values)?.checked_sub(b)?;
+    let difference = value.checked_sub(b)?;
+    if a == 0 || difference % a != 0 {
+        return None;
+    }
+    Some(difference / a)
+}
+
+impl State {
+    // Runs until the path ends, pushing the other side of each symbolic jump onto `pending`
+    fn run(&mut self, limit: u32, pending: &mut Vec<State>) -> PathEnd {
+        loop {
+            if self.steps == limit {
+                return PathEnd::Fault(ExecuteError::ExecutionLimitReached);
+            }
+            match self.step(pending) {
+                Ok(true) => self.steps += 1,
+                Ok(false) => return PathEnd::Halted,
+                Err(end) => return end
+            }
+        }
+    }
+
+    // Performs one instruction, returning false on a halt
+    fn step(&mut self, pending: &mut Vec<State>) -> Result<bool, PathEnd> {
+        let opcode = self.memory.get(State::address(None, self.pc)?).value().ok_or(PathEnd::Symbolic(self.pc))?;
+        if !State::decodes(opcode) {
+            return Err(PathEnd::Fault(ExecuteError::UnrecognisedOpcode(opcode)));
+        }
+        let overflow = PathEnd::Fault(ExecuteError::ArithmeticOverflow);
+        match opcode % 100 {
+            1 | 2 | 7 | 8 => {
+                let a = self.read(opcode, 1)?;
+                let b = self.read(opcode, 2)?;
+                let result = match opcode % 100 {
+                    1 => Expr::add(a, b).ok_or(overflow)?,
+                    2 => Expr::mul(a, b).ok_or(overflow)?,
+                    7 => Expr::lt(a, b),
+                    _ => Expr::eq(a, b),
+                };
+                self.write(opcode, 3, result)?;
+                self.pc = self.next_pc(4)?;
+            },
+            3 => {
+                let value = self.input.pop_front().ok_or(PathEnd::Fault(ExecuteError::InputRequired))?;
+                self.write(opcode, 1, value)?;
+                self.pc = self.next_pc(2)?;
+            },
+            4 => {
+                let value = self.read(opcode, 1)?;
+                self.outputs.push(value);
+                self.pc = self.next_pc(2)?;
+            },
+            5 | 6 => {
+                let condition = self.read(opcode, 1)?;
+                // Whether the jump is taken when the condition is non-zero
+                let on_true = opcode % 100 == 5;
+                let taken = match (condition.value(), self.decided.take()) {
+                    (Some(c), _) => (c != 0) == on_true,
+                    (None, Some(taken)) => taken,
+                    // The other side runs this jump again, not taking it
+                    (None, None) => {
+                        let mut other = self.clone();
+                        other.constraints.push((condition.clone(), !on_true));
+                        other.decided = Some(false);
+                        pending.push(other);
+                        self.constraints.push((condition, on_true));
+                        true
+                    }
+                };
+                // As in the machine, the target is only read when the jump is taken
+                if taken {
+                    let target = self.read(opcode, 2)?;
+                    self.pc = target.value().ok_or(PathEnd::Symbolic(self.pc))?;
+                }
+                else {
+                    self.pc = self.next_pc(3)?;
+                }
+            },
+            9 => {
+                let offset = self.read(opcode, 1)?.value().ok_or(PathEnd::Symbolic(self.pc))?;
+                self.relative_base = self.relative_base.checked_add(offset).ok_or(overflow)?;
+                self.pc = self.next_pc(2)?;
+            },
+            99 => return Ok(false),
+            _ => return Err(PathEnd::Fault(ExecuteError::UnrecognisedOpcode(opcode)))
+        }
+        Ok(true)
+    }
+
+    // Whether the machine would decode the instruction, with the same checks on its mode digits as
+    // Operation::decode
+    fn decodes(opcode: Word) -> bool {
+        let params = opcode / 100;
+        match opcode % 100 {
+            1 | 2 | 7 | 8 => params % 10 <= 2 && (params / 10) % 10 <= 2 && matches!(params / 100, 0 | 2),
+            3 => matches!(params, 0 | 2),
+            4 | 9 => matches!(params, 0..=2),
+            // Digits past the two operands are ignored
+            5 | 6 => params % 10 <= 2 && (params / 10) % 10 <= 2,
+            99 => params == 0,
+            _ => false
+        }
+    }
+
+    fn mode(opcode: Word, n: u32) -> Word {
+        opcode / 10i64.pow(n + 1) % 10
+    }
+
+    fn operand(&self, n: u32) -> Result<Expr, PathEnd> {
+        match self.pc.checked_add(n as Word) {
+            Some(address) => Ok(self.memory.get(address)),
+            None => Err(PathEnd::Fault(ExecuteError::MemoryAccessViolation(Some(self.pc), n as Word)))
+        }
+    }
+
+    fn next_pc(&self, size: Word) -> Result<Word, PathEnd> {
+        advance(self.pc, size).map_err(|e| PathEnd::Fault(e.into()))
+    }
+
+    // The address base + offset, faulting as the machine does when it is out of range
+    fn address(base: Option<Word>, offset: Word) -> Result<Word, PathEnd> {
+        match base.unwrap_or(0).checked_add(offset) {
+            Some(address) if address >= 0 => Ok(address),
+            Some(address) => Err(PathEnd::Fault(ExecuteError::MemoryAccessViolation(base, address))),
+            None => Err(PathEnd::Fault(ExecuteError::MemoryAccessViolation(base, offset)))
+        }
+    }
+
+    fn read(&self, opcode: Word, n: u32) -> Result<Expr, PathEnd> {
+        let operand = self.operand(n)?;
+        let base = match State::mode(opcode, n) {
+            0 => None,
+            1 => return Ok(operand),
+            2 => Some(self.relative_base),
+            _ => return Err(PathEnd::Fault(ExecuteError::UnrecognisedOpcode(opcode)))
+        };
+        match operand.value() {
+            Some(offset) => Ok(self.memory.get(State::address(base, offset)?)),
+            None => {
+                let address = Expr::add(operand, Expr::constant(base.unwrap_or(0))).ok_or(PathEnd::Symbolic(self.pc))?;
+                Ok(Expr(Rc::new(Node::Load(self.memory.clone(), address))))
+            }
+        }
+    }
+
+    fn write(&mut self, opcode: Word, n: u32, value: Expr) -> Result<(), PathEnd> {
+        let operand = self.operand(n)?.value().ok_or(PathEnd::Symbolic(self.pc))?;
+        let base = match State::mode(opcode, n) {
+            0 => None,
+            2 => Some(self.relative_base),
+            _ => return Err(PathEnd::Fault(ExecuteError::UnrecognisedOpcode(opcode)))
+        };
+        let address = State::address(base, operand)?;
+        self.memory.set(address, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, parse_csv, Machine};
+    use crate::fuzz::{FuzzCase, XorShift};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_noun_verb() {
+        // Like day 2: the noun and verb are read as addresses first, then as values, and the result
+        // is 100 * noun + verb + 5
+        let program = parse_csv("1,0,0,3,2,1,17,0,1,0,2,0,1,0,18,0,99,100,5").unwrap();
+        let mut symbolic = Symbolic::new(&program);
+        symbolic.symbolic_memory(1, 0..=99);
+        symbolic.symbolic_memory(2, 0..=99);
+        let paths = symbolic.explore(100, 10);
+        assert_eq!(1, paths.len());
+        assert_eq!("(((s0 * 100) + s1) + 5)", paths[0].memory(0).to_string());
+        assert_eq!("([s0] + [s1])", paths[0].memory(3).to_string());
+        assert_eq!(Some(5), paths[0].memory(3).eval(&[3, 4]));
+
+        assert_eq!(Some(vec![42, 2]), symbolic.solve(Target::Memory(0, 4207), 100, 10));
+        assert_eq!(None, symbolic.solve(Target::Memory(0, 4), 100, 10));
+        assert_eq!(None, symbolic.solve(Target::Memory(0, 10005), 100, 10));
+    }
+
+    #[test]
+    fn test_branches_and_loops() {
+        // Outputs x * 3 when x < 10, otherwise x + 100
+        let program = assemble("
+                IN   x
+                LT   x, #10, t
+                JF   t, #big
+                MUL  x, #3, x
+                OUT  x
+                HLT
+        big:    ADD  x, #100, x
+                OUT  x
+                HLT
+        x:      .data 0
+        t:      .data 0").unwrap();
+        let mut symbolic = Symbolic::new(&program);
+        symbolic.symbolic_input(0..=50);
+        let paths = symbolic.explore(100, 10);
+        assert_eq!(vec![PathEnd::Halted, PathEnd::Halted], paths.iter().map(|p| p.end).collect::<Vec<_>>());
+        // The jump is followed first
+        assert!(paths[0].feasible(&[10]) && !paths[0].feasible(&[9]));
+        assert_eq!(Some(vec![15]), symbolic.solve(Target::Output(0, 115), 100, 10));
+        assert_eq!(Some(vec![9]), symbolic.solve(Target::Output(0, 27), 100, 10));
+        assert_eq!(None, symbolic.solve(Target::Output(0, 30), 100, 10));
+
+        // Adds 7 n times, so each iteration forks on whether the counter has reached zero
+        let program = assemble("
+                IN   n
+        loop:   JF   n, #done
+                ADD  sum, #7, sum
+                ADD  n, #-1, n
+                JT   #1, #loop
+        done:   OUT  sum
+                HLT
+        n:      .data 0
+        sum:    .data 0").unwrap();
+        let mut symbolic = Symbolic::new(&program);
+        symbolic.symbolic_input(0..=20);
+        assert_eq!(Some(vec![9]), symbolic.solve(Target::Output(0, 63), 1000, 100));
+        // Too few instructions to get round the loop nine times
+        assert_eq!(None, symbolic.solve(Target::Output(0, 63), 30, 100));
+        let paths = symbolic.explore(30, 100);
+        assert_eq!(PathEnd::Fault(ExecuteError::ExecutionLimitReached), paths.last().unwrap().end);
+
+        // A symbolic jump target can't be followed
+        let mut symbolic = Symbolic::new(&parse_csv("3,4,1105,1,0,99").unwrap());
+        symbolic.symbolic_input(0..=5);
+        assert_eq!(PathEnd::Symbolic(2), symbolic.explore(10, 10)[0].end);
+    }
+
+    #[test]
+    fn test_decode_matches_machine() {
+        let opcodes = (0..=30000).chain([3003, 1000001, 1000005, 10000099, 210001, 2210008, -1, -99].iter().cloned());
+        for opcode in opcodes {
+            let program = [opcode, 0, 0, 0, 99];
+            let mut machine = Machine::with_io(&program, VecDeque::from(vec![1]), Vec::new());
+            let real = machine.step().map_err(|f| f.error);
+            let mut symbolic = Symbolic::new(&program);
+            symbolic.symbolic_input(0..=1);
+            let end = symbolic.explore(1, 2)[0].end;
+            assert_eq!(real == Err(ExecuteError::UnrecognisedOpcode(opcode)), end == PathEnd::Fault(ExecuteError::UnrecognisedOpcode(opcode)), "opcode {}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_execution_matches_machine() {
+        // The untaken jump's target at -1 is never read
+        let mut cases = vec![FuzzCase{ program: parse_csv("6,7,-1,104,1,99,0,1").unwrap(), input: vec![], limit: 10 }];
+        let mut rng = XorShift::new(0x5eed);
+        cases.extend((0..2000).map(|_| FuzzCase::random(&mut rng)));
+        for case in cases {
+            let mut machine = Machine::with_io(&case.program, VecDeque::from(case.input.clone()), Vec::new());
+            let expected = match machine.execute(case.limit) {
+                Ok(()) => PathEnd::Halted,
+                Err(fault) => PathEnd::Fault(fault.error)
+            };
+            let mut symbolic = Symbolic::new(&case.program);
+            for &w in case.input.iter() {
+                symbolic.input(w);
+            }
+            let paths = symbolic.explore(case.limit, 2);
+            assert_eq!(1, paths.len());
+            let outputs = paths[0].outputs.iter().map(|e| e.value().unwrap()).collect::<Vec<_>>();
+            assert_eq!((expected, machine.output()), (paths[0].end, &outputs), "{:?}", case);
+        }
+
+        // Only the side of a symbolic jump that takes it reads the target
+        let program = parse_csv("3,8,6,8,-1,104,1,99,0").unwrap();
+        let mut symbolic = Symbolic::new(&program);
+        symbolic.symbolic_input(0..=5);
+        let ends = symbolic.explore(10, 10).iter().map(|p| p.end).collect::<Vec<_>>();
+        assert_eq!(vec![PathEnd::Fault(ExecuteError::MemoryAccessViolation(None, -1)), PathEnd::Halted], ends);
+        assert_eq!(Some(vec![1]), symbolic.solve(Target::Output(0, 1), 10, 10));
+    }
+}
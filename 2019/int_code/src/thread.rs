@@ -0,0 +1,76 @@
+use crate::{Machine, Snapshot, StepResult, Fault, WordSource, WordSink};
+use std::thread::{self, JoinHandle};
+
+impl<I: WordSource, O: WordSink> Machine<I, O> {
+    // Runs until the program halts, waiting for input rather than returning InputRequired.
+    // Sources that can't wait, like a VecDeque, still fail when they run dry.
+    pub fn run_blocking(&mut self) -> Result<(), Fault> {
+        self.blocking = true;
+        let result = loop {
+            match self.step() {
+                Ok(StepResult::Executed) => (),
+                Ok(StepResult::Halt) => break Ok(()),
+                Err(e) => break Err(e)
+            }
+        };
+        self.blocking = false;
+        result
+    }
+}
+
+impl<I: WordSource + Send + 'static, O: WordSink + Send + 'static> Machine<I, O> {
+    // Runs the machine to completion on its own thread. The input and output are dropped when it
+    // stops, so a machine reading from this one's output sees its input close.
+    pub fn spawn(mut self) -> JoinHandle<Result<Snapshot, Fault>> {
+        thread::spawn(move || {
+            self.run_blocking()?;
+            Ok(self.snapshot())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Machine, ExecuteError, parse_csv, channel};
+    use std::collections::VecDeque;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_chain() {
+        // Reads a number and outputs it doubled
+        let memory = parse_csv("3,9,102,2,9,10,4,10,99,0,0").unwrap();
+        let (first, input, mut between) = Machine::new(&memory);
+        let mut handles = vec![first.spawn()];
+        for _ in 0..2 {
+            let (output, next) = channel();
+            handles.push(Machine::with_channels(&memory, between, output).spawn());
+            between = next;
+        }
+
+        // Every machine is blocked waiting by the time the input arrives
+        thread::sleep(Duration::from_millis(10));
+        input.send(5).unwrap();
+        assert_eq!(Ok(40), between.recv());
+        for handle in handles {
+            let snapshot = handle.join().unwrap().unwrap();
+            assert_eq!((8, 3), (snapshot.pc(), snapshot.steps()));
+        }
+    }
+
+    #[test]
+    fn test_input_closed() {
+        let memory = parse_csv("3,0,3,0,99").unwrap();
+        let (mut machine, input, _output) = Machine::new(&memory);
+        let sender = thread::spawn(move || {
+            input.send(1).unwrap();
+            thread::sleep(Duration::from_millis(10));
+        });
+        let fault = machine.run_blocking().unwrap_err();
+        assert_eq!((ExecuteError::InputError, 2), (fault.error, fault.pc));
+        sender.join().unwrap();
+
+        let mut machine = Machine::with_io(&memory, VecDeque::new(), Vec::new());
+        assert_eq!(Err(ExecuteError::InputRequired), machine.run_blocking().map_err(|f| f.error));
+    }
+}
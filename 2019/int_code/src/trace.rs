@@ -0,0 +1,262 @@
+use crate::{Machine, Operation, StepResult, StepError, ExecuteError, WordSource, WordSink, Instruction, Mnemonic, Word};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Write;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum IoEvent {
+    Input(Word),
+    Output(Word)
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct TraceEntry {
+    // Number of instructions executed before this one
+    pub step: u64,
+    pub pc: Word,
+    pub relative_base: Word,
+    pub instruction: Instruction,
+    // Values of the operands that were read, in operand order
+    pub values: Vec<Word>,
+    // Address and new value of any memory write
+    pub write: Option<(Word, Word)>,
+    pub io: Option<IoEvent>,
+    // Why the instruction failed, in which case it had no effect and `values` stops at any operand
+    // that couldn't be read
+    pub error: Option<ExecuteError>
+}
+
+pub struct Tracer {
+    sink: Sink
+}
+
+enum Sink {
+    Ring(VecDeque<TraceEntry>, usize),
+    Lines(Box<dyn Write + Send>)
+}
+
+impl Tracer {
+    // Keeps the most recent `capacity` entries in memory
+    pub fn ring(capacity: usize) -> Tracer {
+        Tracer{ sink: Sink::Ring(VecDeque::with_capacity(capacity), capacity) }
+    }
+
+    // Writes every entry as a line of text. Write failures are ignored rather than stopping the machine.
+    pub fn lines<W: Write + Send + 'static>(writer: W) -> Tracer {
+        Tracer{ sink: Sink::Lines(Box::new(writer)) }
+    }
+
+    // Recorded entries, oldest first. Always empty for a line tracer.
+    pub fn entries(&self) -> impl Iterator<Item=&TraceEntry> {
+        let entries = match &self.sink {
+            Sink::Ring(entries, _) => Some(entries.iter()),
+            Sink::Lines(_) => None
+        };
+        entries.into_iter().flatten()
+    }
+
+    fn record(&mut self, entry: TraceEntry) {
+        match &mut self.sink {
+            Sink::Ring(entries, capacity) => {
+                if *capacity == 0 {
+                    return;
+                }
+                if entries.len() == *capacity {
+                    entries.pop_front();
+                }
+                entries.push_back(entry);
+            },
+            Sink::Lines(writer) => {
+                let _ = writeln!(writer, "{}", entry);
+            }
+        }
+    }
+}
+
+impl<I, O> Machine<I, O> {
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn tracer(&self) -> Option<&Tracer> {
+        self.tracer.as_ref()
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+}
+
+impl<I: WordSource, O: WordSink> Machine<I, O> {
+    pub(crate) fn perform_traced(&mut self, op: Operation) -> Result<StepResult, StepError> {
+        let pc = self.pc;
+        let relative_base = self.relative_base;
+        let mut values = Vec::new();
+        let mut read = Ok(());
+        for &p in op.reads(&self.memory).iter().flatten() {
+            match self.memory.read(p) {
+                Ok(value) => values.push(value),
+                Err(e) => {
+                    read = Err(StepError::from(e));
+                    break;
+                }
+            }
+        }
+        let instruction = Instruction{
+            address: pc as usize,
+            opcode: self.memory.read_position(pc)?,
+            mnemonic: op.mnemonic(),
+            operands: op.operands()
+        };
+
+        let result = read.and_then(|()| self.perform(op));
+
+        let (write, io, error) = match &result {
+            // The instruction runs again once there is input, and is traced then
+            Err(StepError::InputRequired) | Err(StepError::NoProgress) => return result,
+            Err(e) => (None, None, Some(ExecuteError::from(e.clone()))),
+            Ok(_) => {
+                let write = op.output()
+                    .and_then(|out| out.address())
+                    .and_then(|a| self.memory.read_position(a).ok().map(|v| (a, v)));
+                let io = match instruction.mnemonic {
+                    Mnemonic::In => write.map(|(_, v)| IoEvent::Input(v)),
+                    Mnemonic::Out => Some(IoEvent::Output(values[0])),
+                    Mnemonic::Ext(_) => self.handler_input.map(IoEvent::Input),
+                    _ => None
+                };
+                (write, io, None)
+            }
+        };
+        let entry = TraceEntry{ step: self.steps, pc, relative_base, instruction, values, write, io, error };
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(entry);
+        }
+        result
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>8} rb={:<6}{}", self.step, self.relative_base, self.instruction)?;
+        if !self.values.is_empty() {
+            let values = self.values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            write!(f, " ; values {}", values.join(", "))?;
+        }
+        if let Some((address, value)) = self.write {
+            write!(f, " ; [{}] = {}", address, value)?;
+        }
+        match self.io {
+            Some(IoEvent::Input(v)) => write!(f, " ; in {}", v)?,
+            Some(IoEvent::Output(v)) => write!(f, " ; out {}", v)?,
+            None => ()
+        }
+        match self.error {
+            Some(e) => write!(f, " ; error {}", e),
+            None => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_csv;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_ring() {
+        let memory = parse_csv("3,9,1001,9,5,9,4,9,99,0").unwrap();
+        let mut machine = Machine::with_io(&memory, VecDeque::from(vec![37]), Vec::new());
+        machine.set_tracer(Tracer::ring(3));
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(3, machine.steps());
+
+        let entries = machine.tracer().unwrap().entries().collect::<Vec<_>>();
+        assert_eq!(3, entries.len());
+        assert_eq!((1, 2, Mnemonic::Add), (entries[0].step, entries[0].pc, entries[0].instruction.mnemonic));
+        assert_eq!(vec![37, 5], entries[0].values);
+        assert_eq!(Some((9, 42)), entries[0].write);
+        assert_eq!(Some(IoEvent::Output(42)), entries[1].io);
+        assert_eq!(Mnemonic::Hlt, entries[2].instruction.mnemonic);
+    }
+
+    #[test]
+    fn test_history_before_failure() {
+        // Walks the relative base down by one until it goes negative
+        let memory = parse_csv("109,2,109,-1,21101,0,0,0,1105,1,2").unwrap();
+        let mut machine = Machine::with_io(&memory, VecDeque::new(), Vec::new());
+        machine.set_tracer(Tracer::ring(3));
+        let error = ExecuteError::MemoryAccessViolation(Some(-1), -1);
+        assert_eq!(Err(error), machine.execute(100).map_err(|f| f.error));
+        let entries = machine.take_tracer().unwrap().entries().cloned().collect::<Vec<_>>();
+        assert_eq!(Mnemonic::Jt, entries[0].instruction.mnemonic);
+        assert_eq!((Mnemonic::Arb, -1), (entries[1].instruction.mnemonic, entries[1].relative_base + entries[1].values[0]));
+        // The faulting instruction comes last, with the operands it read and the error
+        assert_eq!((4, Mnemonic::Add, vec![0, 0]), (entries[2].pc, entries[2].instruction.mnemonic, entries[2].values.clone()));
+        assert_eq!((None, Some(error)), (entries[2].write, entries[2].error));
+    }
+
+    #[test]
+    fn test_failing_instructions() {
+        // An overflowing ADD, then an operand read from a negative address
+        for (program, values) in [("1101,9223372036854775807,1,0,99", vec![Word::MAX, 1]),
+                                   ("1001,-4,1,0,99", vec![])].iter() {
+            let mut machine = Machine::with_io(&parse_csv(program).unwrap(), VecDeque::new(), Vec::new());
+            machine.set_tracer(Tracer::ring(10));
+            let error = machine.execute(10).unwrap_err().error;
+            let entries = machine.tracer().unwrap().entries().collect::<Vec<_>>();
+            assert_eq!(1, entries.len());
+            assert_eq!((0, values, Some(error)), (entries[0].pc, &entries[0].values, entries[0].error));
+        }
+
+        // Waiting for input isn't a failure, and the IN is traced once it runs
+        let mut machine = Machine::with_io(&parse_csv("3,0,99").unwrap(), VecDeque::new(), Vec::new());
+        machine.set_tracer(Tracer::ring(10));
+        assert_eq!(Err(ExecuteError::InputRequired), machine.execute(10).map_err(|f| f.error));
+        machine.input_mut().push_back(4);
+        assert_eq!(Ok(()), machine.execute(10));
+        let entries = machine.tracer().unwrap().entries().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(vec!["       0 rb=0          0  IN   0 ; [0] = 4 ; in 4", "       1 rb=0          2  HLT"], entries);
+    }
+
+    #[test]
+    fn test_untaken_jump() {
+        // The JT isn't taken, so its target at -1 is never read, traced or not
+        let memory = parse_csv("5,4,-1,99,0").unwrap();
+        let mut machine = Machine::with_io(&memory, VecDeque::new(), Vec::new());
+        assert_eq!(Ok(()), machine.execute(10));
+        let mut traced = Machine::with_io(&memory, VecDeque::new(), Vec::new());
+        traced.set_tracer(Tracer::ring(10));
+        assert_eq!(Ok(()), traced.execute(10));
+        assert_eq!(vec![0], traced.tracer().unwrap().entries().next().unwrap().values);
+    }
+
+    #[derive(Clone)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_lines() {
+        let memory = parse_csv("3,7,104,-3,4,7,99,0").unwrap();
+        let buffer = Shared(Arc::new(Mutex::new(Vec::new())));
+        let mut machine = Machine::with_io(&memory, VecDeque::from(vec![5]), Vec::new());
+        machine.set_tracer(Tracer::lines(buffer.clone()));
+        assert_eq!(Ok(()), machine.execute(100));
+        assert_eq!(0, machine.tracer().unwrap().entries().count());
+
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(concat!(
+            "       0 rb=0          0  IN   7 ; [7] = 5 ; in 5\n",
+            "       1 rb=0          2  OUT  #-3 ; values -3 ; out -3\n",
+            "       2 rb=0          4  OUT  7 ; values 5 ; out 5\n",
+            "       3 rb=0          6  HLT\n"), text);
+    }
+}